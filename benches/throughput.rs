@@ -0,0 +1,110 @@
+//! Measures how many lines and bytes of synthetic Winston records per second
+//! go through each stage of the pipeline. Run with `cargo bench`; the records
+//! are about 200 bytes each.
+
+use std::io;
+
+use criterion::{
+  criterion_group, criterion_main, measurement::WallTime, BatchSize, BenchmarkGroup, Criterion,
+  Throughput,
+};
+use winstonjson::{
+  color::{set_color_level, ColorLevel},
+  filter::Filter,
+  input::{read_lines, LineSink},
+  level::Levels,
+  printer::Printer,
+  record::Record,
+  render::Renderer,
+  template::Template,
+};
+
+const LINES: usize = 10_000;
+
+/// Records like those of an HTTP service using Winston's JSON format.
+fn records() -> Vec<u8> {
+  let levels = [
+    "info", "info", "info", "info", "warn", "error", "debug", "http",
+  ];
+  let mut out = Vec::new();
+  for i in 0..LINES {
+    let line = format!(
+      r#"{{"level":"{}","message":"request handled for user {}","service":"api","req":{{"method":"GET","url":"/api/v1/items/{i}","durationMs":{}}},"pid":4242,"timestamp":"2026-10-16T10:{:02}:{:02}.{:03}Z"}}"#,
+      levels[i % levels.len()],
+      i * 7 % 9999,
+      i * 13 % 2000,
+      i / 60_000 % 60,
+      i / 1000 % 60,
+      i % 1000,
+    );
+    out.extend_from_slice(line.as_bytes());
+    out.push(b'\n');
+  }
+  out
+}
+
+struct Parse;
+
+impl LineSink for Parse {
+  fn line(&mut self, _source: usize, line: &[u8]) -> io::Result<()> {
+    std::hint::black_box(Record::parse(line));
+    Ok(())
+  }
+}
+
+fn throughput(c: &mut Criterion) {
+  let input = records();
+  // Criterion reports a single throughput per group.
+  for (name, throughput) in [
+    ("lines", Throughput::Elements(LINES as u64)),
+    ("bytes", Throughput::Bytes(input.len() as u64)),
+  ] {
+    let mut group = c.benchmark_group(name);
+    group.throughput(throughput);
+    stages(&mut group, &input);
+    group.finish();
+  }
+}
+
+fn stages(group: &mut BenchmarkGroup<WallTime>, input: &[u8]) {
+  group.bench_function("parse", |b| {
+    b.iter(|| read_lines(input, 0, &mut Parse, || false).unwrap())
+  });
+
+  for (name, level) in [
+    ("render", ColorLevel::None),
+    ("render_colored", ColorLevel::Basic),
+  ] {
+    group.bench_function(name, |b| {
+      set_color_level(level);
+      b.iter_batched(
+        || {
+          let renderer = Renderer::new(Levels::npm(), Template::default());
+          Printer::new(io::sink(), Filter::new(), renderer)
+        },
+        |mut printer| {
+          read_lines(input, 0, &mut printer, || false).unwrap();
+          printer.flush().unwrap();
+        },
+        BatchSize::LargeInput,
+      )
+    });
+  }
+
+  group.bench_function("filter_level", |b| {
+    set_color_level(ColorLevel::None);
+    let levels = Levels::npm();
+    b.iter_batched(
+      || {
+        let filter = Filter::new().min_level(&levels, "error").unwrap();
+        let renderer = Renderer::new(Levels::npm(), Template::default());
+        Printer::new(io::sink(), filter, renderer)
+      },
+      |mut printer| read_lines(input, 0, &mut printer, || false).unwrap(),
+      BatchSize::LargeInput,
+    )
+  });
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
@@ -0,0 +1,208 @@
+use std::{num::NonZeroUsize, path::PathBuf};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use winstonjson::{
+  color::ColorChoice, level::Levels, meta::MetaMode, output::OutputFormat, printer::NonJson,
+  time::TimeFormat,
+};
+
+/// Colorize Winston JSON logs.
+///
+/// Reads newline-delimited Winston JSON records from the given files, or stdin,
+/// and prints them as colored, human-readable lines.
+#[derive(Debug, Parser)]
+#[command(version, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+  #[command(subcommand)]
+  pub command: Option<Command>,
+
+  /// Log files to read; `-` or no files at all means stdin.
+  ///
+  /// Records from several files are interleaved in timestamp order.
+  #[arg(value_name = "FILE")]
+  pub files: Vec<PathBuf>,
+
+  /// Keep reading lines appended to the files, following them across
+  /// rotation and truncation like `tail -F`. Only new lines are shown.
+  #[arg(short, long)]
+  pub follow: bool,
+
+  /// Show the newest records first, reading the files backward from their
+  /// end.
+  #[arg(long, conflicts_with = "follow")]
+  pub reverse: bool,
+
+  /// Only show the last N records that match the filters, found by reading
+  /// the files backward from their end. With `--follow`, go on with new
+  /// records after them.
+  #[arg(short = 'n', long, value_name = "N")]
+  pub tail: Option<usize>,
+
+  /// The Winston level config the logs were written with.
+  ///
+  /// Defaults to the levels of the config file, or `npm` if it has none.
+  #[arg(long, value_enum, global = true)]
+  pub levels: Option<LevelConfig>,
+
+  /// Concatenate multiple files instead of merging them by timestamp.
+  #[arg(long)]
+  pub no_merge: bool,
+
+  /// Prefix every line with the name of the file it came from.
+  #[arg(short, long)]
+  pub prefix: bool,
+
+  /// Hide records less severe than this level, like a transport's `level`.
+  #[arg(long, value_name = "NAME", global = true)]
+  pub level: Option<String>,
+
+  /// Only show records matching a filter expression.
+  ///
+  /// Fields are referenced by (nested) name and compared with `==`, `!=`, `<`,
+  /// `<=`, `>`, `>=` or matched against a regex with `~` and `!~`. A bare field
+  /// checks that it exists. Combine conditions with `&&`, `||`, `!` and
+  /// parentheses, e.g.
+  /// `service == "billing" && durationMs > 500 && message ~ /timeout/i`.
+  /// May be repeated; every expression must match.
+  #[arg(long, value_name = "EXPR", global = true)]
+  pub filter: Vec<String>,
+
+  /// Only show records logged at or after this time: a timestamp like
+  /// `2026-10-16 12:00` or a duration before now like `15m` or `2d`.
+  ///
+  /// Sorted log files are binary-searched for the start of the window.
+  #[arg(long, value_name = "TIME", global = true)]
+  pub since: Option<String>,
+
+  /// Only show records logged at or before this time, given like `--since`.
+  #[arg(long, value_name = "TIME", global = true)]
+  pub until: Option<String>,
+
+  /// What to do with lines that are not JSON: print them unchanged (`pass`),
+  /// dimmed (`dim`), not at all (`drop`), or `wrap` them into records with the
+  /// level `unknown` that are filtered and rendered like any other.
+  #[arg(
+    long,
+    value_enum,
+    value_name = "POLICY",
+    default_value_t,
+    global = true
+  )]
+  pub non_json: NonJson,
+
+  /// Treat lines that are not JSON as continuations of the record before
+  /// them, e.g. the dump of an uncaught exception, and show and filter them
+  /// along with it.
+  #[arg(long, global = true)]
+  pub join: bool,
+
+  /// Layout of the rendered lines, e.g.
+  /// `{timestamp:dim} [{level:upper:pad5}] {label}: {message} {meta}`.
+  ///
+  /// Placeholders name (nested) fields and take `:`-separated modifiers:
+  /// `padN`/`lpadN` to pad left/right aligned, `truncN` to truncate,
+  /// `upper`/`lower`, or Winston color strings like `bold red`. `{meta}`
+  /// stands for all fields not shown otherwise.
+  #[arg(long, value_name = "TEMPLATE")]
+  pub format: Option<String>,
+
+  /// How to show the metadata fields of a record [default: inline].
+  #[arg(long, value_enum, value_name = "MODE")]
+  pub meta: Option<MetaMode>,
+
+  /// Write the records as colored `text`, or re-emit them as `ndjson`,
+  /// indented `json`, `csv`, `tsv` or `logfmt` for further processing.
+  ///
+  /// Timestamps are written as logged unless `--time-format` or `--tz` is
+  /// given. Lines that are not JSON are left out unless `--non-json wrap` is
+  /// given.
+  #[arg(short, long, value_enum, value_name = "FORMAT", default_value_t)]
+  pub output: OutputFormat,
+
+  /// The (nested) fields to write with `--output`, separated by commas.
+  ///
+  /// Defaults to `timestamp,level,message` for CSV and TSV and to all fields
+  /// otherwise.
+  #[arg(long, value_name = "FIELDS", value_delimiter = ',')]
+  pub columns: Vec<String>,
+
+  /// How to show timestamps: `original` (as logged), a strftime-like format
+  /// such as `%H:%M:%S%.3f`, `iso`, `relative` (e.g. `3s ago`) or `elapsed`
+  /// (time since the first record).
+  ///
+  /// Defaults to `original`, or `iso` if `--tz` is given.
+  #[arg(long, value_name = "FORMAT", global = true)]
+  pub time_format: Option<TimeFormat>,
+
+  /// Time zone to show timestamps in: `local`, `utc` or an IANA name like
+  /// `Europe/Berlin` [default: local].
+  #[arg(long, value_name = "ZONE", global = true)]
+  pub tz: Option<String>,
+
+  /// Number of threads to parse, filter and render files with [default: the
+  /// number of CPUs].
+  ///
+  /// Output keeps the order of the input either way; stdin and `--follow` are
+  /// always read on a single thread.
+  #[arg(short = 'j', long, value_name = "N")]
+  pub threads: Option<NonZeroUsize>,
+
+  /// When to use colors: `auto` uses them if the output is a terminal and
+  /// neither `NO_COLOR` nor `TERM=dumb` are set, or if `FORCE_COLOR` or
+  /// `CLICOLOR_FORCE` are.
+  ///
+  /// The number of colors is taken from `COLORTERM` and `TERM`; colors beyond
+  /// what the terminal supports are replaced with the closest ones it has.
+  #[arg(long, value_enum, value_name = "WHEN", default_value_t, global = true)]
+  pub color: ColorChoice,
+
+  /// The colors to use: `default`, `dark`, `light`, `solarized` or a theme
+  /// defined in the config file.
+  #[arg(long, value_name = "NAME", global = true)]
+  pub theme: Option<String>,
+
+  /// Path of the config file with custom levels and colors.
+  ///
+  /// Defaults to `$XDG_CONFIG_HOME/winstonjson/config.json`.
+  #[arg(long, value_name = "PATH", global = true)]
+  pub config: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+  /// Browse logs in an interactive, full-screen terminal UI.
+  ///
+  /// Scroll with the arrow keys, PageUp/PageDown, `g` and `G`, toggle levels in
+  /// the sidebar (Tab), search with `/`, `n` and `N`, show the full JSON of a
+  /// record with Enter and toggle columns with `1` to `4`. `q` quits.
+  Tui(TuiArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct TuiArgs {
+  /// Log files to read; `-` or no files at all means stdin.
+  #[arg(value_name = "FILE")]
+  pub files: Vec<PathBuf>,
+
+  /// Keep reading lines appended to the files after loading them.
+  #[arg(short, long)]
+  pub follow: bool,
+}
+
+/// The level configs built into Winston.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum LevelConfig {
+  Npm,
+  Syslog,
+  Cli,
+}
+
+impl LevelConfig {
+  pub fn levels(self) -> Levels {
+    match self {
+      LevelConfig::Npm => Levels::npm(),
+      LevelConfig::Syslog => Levels::syslog(),
+      LevelConfig::Cli => Levels::cli(),
+    }
+  }
+}
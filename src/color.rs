@@ -0,0 +1,281 @@
+//! Deciding whether and how many colors to use, from the `--color` option, the
+//! environment and whether the output is a terminal.
+
+use std::{
+  env,
+  ffi::OsString,
+  sync::atomic::{AtomicU8, Ordering},
+};
+
+/// How many colors the output supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorLevel {
+  /// No escape sequences at all.
+  None,
+  /// The 16 basic ANSI colors.
+  Basic,
+  /// The 256-color xterm palette.
+  Ansi256,
+  /// 24-bit RGB colors.
+  TrueColor,
+}
+
+impl ColorLevel {
+  fn from_u8(level: u8) -> ColorLevel {
+    match level {
+      0 => ColorLevel::None,
+      1 => ColorLevel::Basic,
+      2 => ColorLevel::Ansi256,
+      _ => ColorLevel::TrueColor,
+    }
+  }
+}
+
+static COLOR_LEVEL: AtomicU8 = AtomicU8::new(ColorLevel::Basic as u8);
+
+/// Sets the color level that all styled text is painted with.
+pub fn set_color_level(level: ColorLevel) {
+  COLOR_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// The color level set with [`set_color_level`], basic colors by default.
+pub fn color_level() -> ColorLevel {
+  ColorLevel::from_u8(COLOR_LEVEL.load(Ordering::Relaxed))
+}
+
+/// When to use colors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+  /// If the output is a terminal, unless the environment says otherwise.
+  #[default]
+  Auto,
+  Always,
+  Never,
+}
+
+impl ColorChoice {
+  /// Decides the color level of output that goes to a terminal or not.
+  ///
+  /// With `auto`, like in Node.js, `FORCE_COLOR` (`0` to disable, `1`, `2` or
+  /// `3` for 16, 256 or 16 million colors) takes precedence over `NO_COLOR`,
+  /// followed by `CLICOLOR_FORCE`, the terminal check and `TERM=dumb`. How
+  /// many colors a terminal supports is told by `COLORTERM` and `TERM`.
+  pub fn level(self, is_terminal: bool) -> ColorLevel {
+    self.level_in(is_terminal, |name| env::var_os(name))
+  }
+
+  /// Like [`level`](Self::level), with environment variables looked up by
+  /// `var`.
+  fn level_in(self, is_terminal: bool, var: impl Fn(&str) -> Option<OsString>) -> ColorLevel {
+    let terminal_level = || terminal_level(&var);
+    match self {
+      ColorChoice::Never => return ColorLevel::None,
+      ColorChoice::Always => return terminal_level().max(ColorLevel::Basic),
+      ColorChoice::Auto => {}
+    }
+    if let Some(force) = var("FORCE_COLOR") {
+      return match force.to_str().unwrap_or_default() {
+        "0" | "false" => ColorLevel::None,
+        "2" => ColorLevel::Ansi256,
+        "3" => ColorLevel::TrueColor,
+        _ => terminal_level().max(ColorLevel::Basic),
+      };
+    }
+    if var("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+      return ColorLevel::None;
+    }
+    if var("CLICOLOR_FORCE").is_some_and(|value| !value.is_empty() && value != "0") {
+      return terminal_level().max(ColorLevel::Basic);
+    }
+    if !is_terminal {
+      return ColorLevel::None;
+    }
+    terminal_level()
+  }
+}
+
+/// The colors the terminal supports according to `COLORTERM` and `TERM`.
+fn terminal_level(var: impl Fn(&str) -> Option<OsString>) -> ColorLevel {
+  let term = var("TERM").unwrap_or_default();
+  if term == "dumb" {
+    return ColorLevel::None;
+  }
+  match var("COLORTERM").as_ref().and_then(|value| value.to_str()) {
+    Some("truecolor" | "24bit") => ColorLevel::TrueColor,
+    _ if term.to_string_lossy().contains("256color") => ColorLevel::Ansi256,
+    _ => ColorLevel::Basic,
+  }
+}
+
+/// The default RGB values of the 16 basic colors in xterm.
+const BASIC_RGB: [(u8, u8, u8); 16] = [
+  (0, 0, 0),
+  (205, 0, 0),
+  (0, 205, 0),
+  (205, 205, 0),
+  (0, 0, 238),
+  (205, 0, 205),
+  (0, 205, 205),
+  (229, 229, 229),
+  (127, 127, 127),
+  (255, 0, 0),
+  (0, 255, 0),
+  (255, 255, 0),
+  (92, 92, 255),
+  (255, 0, 255),
+  (0, 255, 255),
+  (255, 255, 255),
+];
+
+/// The intensities of the 6×6×6 color cube of the 256-color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The RGB value of a color of the 256-color palette.
+pub fn ansi256_to_rgb(index: u8) -> (u8, u8, u8) {
+  match index {
+    0..16 => BASIC_RGB[index as usize],
+    16..232 => {
+      let i = index - 16;
+      (
+        CUBE_LEVELS[(i / 36) as usize],
+        CUBE_LEVELS[(i / 6 % 6) as usize],
+        CUBE_LEVELS[(i % 6) as usize],
+      )
+    }
+    _ => {
+      let gray = 8 + 10 * (index - 232);
+      (gray, gray, gray)
+    }
+  }
+}
+
+/// The closest color of the 256-color palette, from the color cube or the
+/// gray ramp.
+pub fn rgb_to_ansi256(rgb: (u8, u8, u8)) -> u8 {
+  let nearest_level = |c: u8| {
+    (0..6)
+      .min_by_key(|&i| CUBE_LEVELS[i].abs_diff(c))
+      .expect("levels are not empty") as u8
+  };
+  let (r, g, b) = (
+    nearest_level(rgb.0),
+    nearest_level(rgb.1),
+    nearest_level(rgb.2),
+  );
+  let cube = 16 + 36 * r + 6 * g + b;
+  let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+  let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+  [cube, gray]
+    .into_iter()
+    .min_by_key(|&index| distance(ansi256_to_rgb(index), rgb))
+    .expect("candidates are not empty")
+}
+
+/// The index of the closest of the 16 basic colors.
+pub fn rgb_to_basic(rgb: (u8, u8, u8)) -> u8 {
+  (0..16)
+    .min_by_key(|&index| distance(BASIC_RGB[index as usize], rgb))
+    .expect("palette is not empty")
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+  let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+  d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::style::Color;
+
+  fn level(choice: ColorChoice, is_terminal: bool, vars: &[(&str, &str)]) -> ColorLevel {
+    choice.level_in(is_terminal, |name| {
+      vars
+        .iter()
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.into())
+    })
+  }
+
+  #[test]
+  fn rgb_is_matched_to_the_nearest_palette_color() {
+    assert_eq!(rgb_to_ansi256((255, 0, 0)), 196);
+    assert_eq!(rgb_to_ansi256((0, 0, 0)), 16);
+    assert_eq!(rgb_to_ansi256((95, 135, 175)), 67);
+    assert_eq!(rgb_to_ansi256((128, 128, 128)), 244);
+    assert_eq!(rgb_to_ansi256((238, 238, 238)), 255);
+    assert_eq!(rgb_to_basic((250, 10, 10)), 9);
+    assert_eq!(rgb_to_basic((0, 200, 0)), 2);
+    assert_eq!(rgb_to_basic((130, 130, 130)), 8);
+    assert_eq!(rgb_to_basic((0, 0, 0)), 0);
+  }
+
+  #[test]
+  fn colors_are_degraded_to_the_level() {
+    let orange = Color::Rgb(255, 135, 0);
+    assert_eq!(orange.degrade(ColorLevel::TrueColor), orange);
+    assert_eq!(orange.degrade(ColorLevel::Ansi256), Color::Fixed(208));
+    assert_eq!(orange.degrade(ColorLevel::Basic), Color::Yellow);
+    assert_eq!(
+      Color::Fixed(46).degrade(ColorLevel::Ansi256),
+      Color::Fixed(46)
+    );
+    assert_eq!(
+      Color::Fixed(46).degrade(ColorLevel::Basic),
+      Color::BrightGreen
+    );
+    assert_eq!(Color::Red.degrade(ColorLevel::Basic), Color::Red);
+  }
+
+  #[test]
+  fn choices_override_the_environment() {
+    let vars = [("FORCE_COLOR", "3"), ("TERM", "xterm-256color")];
+    assert_eq!(level(ColorChoice::Never, true, &vars), ColorLevel::None);
+    assert_eq!(level(ColorChoice::Always, false, &[]), ColorLevel::Basic);
+    assert_eq!(
+      level(ColorChoice::Always, false, &[("TERM", "dumb")]),
+      ColorLevel::Basic
+    );
+    assert_eq!(
+      level(ColorChoice::Always, false, &vars),
+      ColorLevel::Ansi256
+    );
+  }
+
+  #[test]
+  fn environment_precedence() {
+    let auto = |is_terminal, vars: &[(&str, &str)]| level(ColorChoice::Auto, is_terminal, vars);
+    // FORCE_COLOR wins over NO_COLOR and the terminal check.
+    assert_eq!(
+      auto(false, &[("FORCE_COLOR", "2"), ("NO_COLOR", "1")]),
+      ColorLevel::Ansi256
+    );
+    assert_eq!(
+      auto(true, &[("FORCE_COLOR", "0"), ("CLICOLOR_FORCE", "1")]),
+      ColorLevel::None
+    );
+    assert_eq!(auto(false, &[("FORCE_COLOR", "")]), ColorLevel::Basic);
+    // NO_COLOR wins over CLICOLOR_FORCE, unless it is empty.
+    assert_eq!(
+      auto(true, &[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")]),
+      ColorLevel::None
+    );
+    assert_eq!(auto(true, &[("NO_COLOR", "")]), ColorLevel::Basic);
+    // CLICOLOR_FORCE colors output that is not a terminal.
+    assert_eq!(auto(false, &[("CLICOLOR_FORCE", "1")]), ColorLevel::Basic);
+    assert_eq!(auto(false, &[("CLICOLOR_FORCE", "0")]), ColorLevel::None);
+    assert_eq!(auto(false, &[]), ColorLevel::None);
+    // TERM=dumb turns colors off, COLORTERM and TERM tell how many there are.
+    assert_eq!(auto(true, &[("TERM", "dumb")]), ColorLevel::None);
+    assert_eq!(
+      auto(true, &[("TERM", "xterm-256color")]),
+      ColorLevel::Ansi256
+    );
+    assert_eq!(
+      auto(true, &[("TERM", "xterm"), ("COLORTERM", "truecolor")]),
+      ColorLevel::TrueColor
+    );
+    assert_eq!(auto(true, &[("COLORTERM", "24bit")]), ColorLevel::TrueColor);
+    assert_eq!(auto(true, &[("TERM", "xterm")]), ColorLevel::Basic);
+  }
+}
@@ -0,0 +1,153 @@
+use std::{
+  collections::HashMap,
+  env, fs, io,
+  path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::{
+  level::{Level, Levels},
+  meta::MetaMode,
+  style::Style,
+  theme::ThemeConfig,
+};
+
+/// The contents of `config.json`.
+///
+/// `levels` and `colors` have the same shape as the objects passed to
+/// `winston.createLogger({ levels })` and `winston.addColors()`; `format`,
+/// `meta` and `theme` are defaults for the corresponding command line options,
+/// and `themes` defines custom themes:
+///
+/// ```json
+/// {
+///   "levels": { "audit": 0, "error": 1, "warn": 2, "info": 3 },
+///   "colors": { "audit": "bold red whiteBG", "info": "green" },
+///   "format": "{timestamp} [{level:pad5}] {message} {meta}",
+///   "meta": "expanded",
+///   "theme": "mine",
+///   "themes": { "mine": { "base": "light", "key": "#005f87" } }
+/// }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+  pub levels: Option<HashMap<String, u32>>,
+  pub colors: HashMap<String, Style>,
+  pub format: Option<String>,
+  pub meta: Option<MetaMode>,
+  pub theme: Option<String>,
+  pub themes: HashMap<String, ThemeConfig>,
+}
+
+impl Config {
+  /// Loads the config from `path`, or from the default location if no path is
+  /// given. A missing default config is not an error.
+  pub fn load(path: Option<&Path>) -> anyhow::Result<Config> {
+    let (path, required) = match path {
+      Some(path) => (path.to_owned(), true),
+      None => match default_path() {
+        Some(path) => (path, false),
+        None => return Ok(Config::default()),
+      },
+    };
+    let text = match fs::read_to_string(&path) {
+      Ok(text) => text,
+      Err(err) if err.kind() == io::ErrorKind::NotFound && !required => {
+        return Ok(Config::default())
+      }
+      Err(err) => return Err(err).with_context(|| format!("failed to read {}", path.display())),
+    };
+    serde_json::from_str(&text).with_context(|| format!("invalid config {}", path.display()))
+  }
+
+  /// The custom level set, if the config defines one. Levels are ordered by
+  /// priority, most severe first.
+  pub fn levels(&self) -> Option<Levels> {
+    let mut levels: Vec<Level> = self
+      .levels
+      .as_ref()?
+      .iter()
+      .map(|(name, &priority)| Level {
+        name: name.clone(),
+        priority,
+        style: Style::new(),
+      })
+      .collect();
+    levels.sort_by(|a, b| {
+      a.priority
+        .cmp(&b.priority)
+        .then_with(|| a.name.cmp(&b.name))
+    });
+    Some(Levels::new(levels))
+  }
+
+  /// Overrides the styles of `levels` with the configured colors.
+  pub fn apply_colors(&self, levels: &mut Levels) {
+    for (name, &style) in &self.colors {
+      levels.set_style(name, style);
+    }
+  }
+}
+
+/// `$XDG_CONFIG_HOME/winstonjson/config.json`, falling back to
+/// `~/.config/winstonjson/config.json`.
+fn default_path() -> Option<PathBuf> {
+  let config_dir = env::var_os("XDG_CONFIG_HOME")
+    .filter(|dir| !dir.is_empty())
+    .map(PathBuf::from)
+    .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+  Some(config_dir.join("winstonjson").join("config.json"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::style::Color;
+
+  fn config(json: &str) -> Config {
+    serde_json::from_str(json).unwrap()
+  }
+
+  #[test]
+  fn levels_are_ordered_by_priority() {
+    let levels =
+      config(r#"{ "levels": { "info": 3, "audit": 0, "warn": 2, "error": 1, "notice": 3 } }"#)
+        .levels()
+        .unwrap();
+    let names: Vec<_> = levels.iter().map(|level| level.name.as_str()).collect();
+    assert_eq!(names, ["audit", "error", "warn", "info", "notice"]);
+    assert!(config("{}").levels().is_none());
+  }
+
+  #[test]
+  fn colors_apply_on_top_of_the_levels() {
+    let config = config(
+      r#"{
+        "levels": { "audit": 0, "error": 1 },
+        "colors": { "audit": "bold red whiteBG", "error": ["italic", "cyan"], "trace": "gray" }
+      }"#,
+    );
+    let mut levels = config.levels().unwrap();
+    config.apply_colors(&mut levels);
+    assert_eq!(
+      levels.style("audit"),
+      Style::new().fg(Color::Red).bg(Color::White).bold()
+    );
+    assert_eq!(
+      levels.style("error"),
+      Style {
+        italic: true,
+        ..Style::new().fg(Color::Cyan)
+      }
+    );
+    assert!(levels.get("trace").is_none());
+
+    let mut npm = Levels::npm();
+    config.apply_colors(&mut npm);
+    assert_eq!(npm.style("error"), levels.style("error"));
+    assert_eq!(npm.style("info"), Style::new().fg(Color::Green));
+  }
+}
@@ -0,0 +1,162 @@
+use std::cmp::Ordering;
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::record::{FieldPath, Record};
+
+/// A parsed `--filter` expression.
+#[derive(Debug, Clone)]
+pub enum Expr {
+  And(Box<Expr>, Box<Expr>),
+  Or(Box<Expr>, Box<Expr>),
+  Not(Box<Expr>),
+  /// A bare field reference: true if the field is present and not `null`.
+  Exists(FieldPath),
+  Compare(Operand, CompareOp, Operand),
+  /// `operand ~ /regex/`.
+  Matches(Operand, Regex),
+}
+
+#[derive(Debug, Clone)]
+pub enum Operand {
+  Field(FieldPath),
+  Literal(Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+  Eq,
+  Ne,
+  Lt,
+  Le,
+  Gt,
+  Ge,
+}
+
+impl Expr {
+  pub fn eval(&self, record: &Record) -> bool {
+    match self {
+      Expr::And(a, b) => a.eval(record) && b.eval(record),
+      Expr::Or(a, b) => a.eval(record) || b.eval(record),
+      Expr::Not(expr) => !expr.eval(record),
+      Expr::Exists(path) => record.lookup(path).is_some_and(|value| !value.is_null()),
+      Expr::Compare(a, op, b) => compare(a.resolve(record), *op, b.resolve(record)),
+      Expr::Matches(operand, regex) => match operand.resolve(record) {
+        Some(Value::String(s)) => regex.is_match(s),
+        Some(Value::Null) | None => false,
+        Some(other) => regex.is_match(&other.to_string()),
+      },
+    }
+  }
+}
+
+impl Operand {
+  fn resolve<'a>(&'a self, record: &'a Record) -> Option<&'a Value> {
+    match self {
+      Operand::Field(path) => record.lookup(path),
+      Operand::Literal(value) => Some(value),
+    }
+  }
+}
+
+/// Missing fields compare like `null`. Numbers and numeric strings compare
+/// numerically, strings lexicographically; any other ordering is false.
+fn compare(a: Option<&Value>, op: CompareOp, b: Option<&Value>) -> bool {
+  let a = a.unwrap_or(&Value::Null);
+  let b = b.unwrap_or(&Value::Null);
+  match op {
+    CompareOp::Eq => equal(a, b),
+    CompareOp::Ne => !equal(a, b),
+    CompareOp::Lt => order(a, b) == Some(Ordering::Less),
+    CompareOp::Le => matches!(order(a, b), Some(Ordering::Less | Ordering::Equal)),
+    CompareOp::Gt => order(a, b) == Some(Ordering::Greater),
+    CompareOp::Ge => matches!(order(a, b), Some(Ordering::Greater | Ordering::Equal)),
+  }
+}
+
+fn equal(a: &Value, b: &Value) -> bool {
+  match (as_number(a), as_number(b)) {
+    (Some(x), Some(y)) if a.is_number() || b.is_number() => x == y,
+    _ => a == b,
+  }
+}
+
+fn order(a: &Value, b: &Value) -> Option<Ordering> {
+  match (a, b) {
+    (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+    _ if a.is_number() || b.is_number() => as_number(a)?.partial_cmp(&as_number(b)?),
+    _ => None,
+  }
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+  match value {
+    Value::Number(n) => n.as_f64(),
+    Value::String(s) => s.trim().parse().ok(),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::filter::parse::parse;
+
+  fn eval(filter: &str, line: &str) -> bool {
+    let expr = parse(filter).unwrap();
+    expr.eval(&Record::parse(line.as_bytes()).unwrap())
+  }
+
+  #[test]
+  fn missing_field_is_like_null() {
+    let missing = r#"{"message":"hi"}"#;
+    let null = r#"{"message":"hi","user":null}"#;
+    for line in [missing, null] {
+      assert!(eval("user == null", line));
+      assert!(!eval("user != null", line));
+      assert!(!eval("user", line));
+      assert!(eval("!user", line));
+      assert!(!eval("user ~ /null/", line));
+      assert!(!eval("user < 1", line));
+    }
+    assert!(eval("user", r#"{"user":false}"#));
+    assert!(eval("user == false", r#"{"user":false}"#));
+    assert!(!eval("user == null", r#"{"user":false}"#));
+  }
+
+  #[test]
+  fn numeric_strings_compare_as_numbers() {
+    assert!(eval("status == 500", r#"{"status":"500"}"#));
+    assert!(eval("status == '500'", r#"{"status":500}"#));
+    assert!(eval("status == 500", r#"{"status":500.0}"#));
+    assert!(eval("status >= 500", r#"{"status":" 503 "}"#));
+    assert!(!eval("status == '500.0'", r#"{"status":"500"}"#));
+    assert!(!eval("status == 500", r#"{"status":"5xx"}"#));
+    assert!(eval("status != 500", r#"{"status":"5xx"}"#));
+    assert!(eval("status ~ /^50/", r#"{"status":503}"#));
+  }
+
+  #[test]
+  fn ordering_between_mixed_types() {
+    // Strings order lexicographically, numbers and numeric strings numerically.
+    assert!(!eval("version < '10'", r#"{"version":"9"}"#));
+    assert!(eval("version < 10", r#"{"version":"9"}"#));
+    assert!(eval("durationMs > '99'", r#"{"durationMs":100}"#));
+    // Any other ordering is false both ways.
+    for line in [
+      r#"{"x":"abc"}"#,
+      r#"{"x":true}"#,
+      r#"{"x":null}"#,
+      r#"{"x":[1]}"#,
+      r#"{"x":{"a":1}}"#,
+      r#"{}"#,
+    ] {
+      for op in ["<", "<=", ">", ">="] {
+        assert!(!eval(&format!("x {op} 1"), line), "x {op} 1 on {line}");
+      }
+    }
+    assert!(!eval("x < 'b'", r#"{"x":true}"#));
+    assert!(!eval("x >= 'b'", r#"{"x":true}"#));
+  }
+}
@@ -0,0 +1,95 @@
+mod expr;
+mod parse;
+
+use anyhow::{anyhow, Context};
+use jiff::Timestamp;
+
+pub use self::{expr::Expr, parse::ParseError};
+use crate::{level::Levels, record::Record, time::parse_timestamp};
+
+/// Decides which records are shown.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+  min_level: Option<MinLevel>,
+  exprs: Vec<Expr>,
+  since: Option<Timestamp>,
+  until: Option<Timestamp>,
+}
+
+/// Only lets through records at least as severe as a given level.
+#[derive(Debug, Clone)]
+struct MinLevel {
+  levels: Levels,
+  priority: u32,
+}
+
+impl Filter {
+  pub fn new() -> Self {
+    Filter::default()
+  }
+
+  /// Hides records whose level is less severe than `name`, exactly like the
+  /// `level` option of a Winston transport. Records with a level outside of
+  /// `levels` are always shown.
+  pub fn min_level(mut self, levels: &Levels, name: &str) -> anyhow::Result<Self> {
+    let level = levels.get(name).ok_or_else(|| {
+      let names: Vec<&str> = levels.iter().map(|level| level.name.as_str()).collect();
+      anyhow!(
+        "unknown level `{name}`, expected one of: {}",
+        names.join(", ")
+      )
+    })?;
+    self.min_level = Some(MinLevel {
+      levels: levels.clone(),
+      priority: level.priority,
+    });
+    Ok(self)
+  }
+
+  /// Only shows records for which the filter expression `source` holds. All
+  /// expressions added this way must hold.
+  pub fn expr(mut self, source: &str) -> anyhow::Result<Self> {
+    let expr = parse::parse(source).with_context(|| format!("invalid filter `{source}`"))?;
+    self.exprs.push(expr);
+    Ok(self)
+  }
+
+  /// Only shows records logged at or after `since` and at or before `until`.
+  /// Records without a timestamp are hidden as soon as either bound is set.
+  pub fn time_window(mut self, since: Option<Timestamp>, until: Option<Timestamp>) -> Self {
+    self.since = since;
+    self.until = until;
+    self
+  }
+
+  pub fn has_time_window(&self) -> bool {
+    self.since.is_some() || self.until.is_some()
+  }
+
+  /// Whether `record` lies within the time window, if one is set.
+  pub fn in_time_window(&self, record: &Record) -> bool {
+    if !self.has_time_window() {
+      return true;
+    }
+    let Some(timestamp) = record.timestamp().and_then(parse_timestamp) else {
+      return false;
+    };
+    !(self.since.is_some_and(|since| timestamp < since)
+      || self.until.is_some_and(|until| timestamp > until))
+  }
+
+  pub fn matches(&self, record: &Record) -> bool {
+    if let Some(min_level) = &self.min_level {
+      let priority = record
+        .level()
+        .and_then(|name| min_level.levels.get(&name).map(|level| level.priority));
+      if priority.is_some_and(|priority| priority > min_level.priority) {
+        return false;
+      }
+    }
+    if !self.in_time_window(record) {
+      return false;
+    }
+    self.exprs.iter().all(|expr| expr.eval(record))
+  }
+}
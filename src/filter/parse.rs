@@ -0,0 +1,430 @@
+//! Parser for filter expressions such as
+//! `service == "billing" && durationMs > 500 && message ~ /timeout/i`.
+//!
+//! ```text
+//! expr       = and ("||" and)*
+//! and        = unary ("&&" unary)*
+//! unary      = "!" unary | "(" expr ")" | comparison
+//! comparison = operand (op operand | ("~" | "!~") (regex | string))?
+//! operand    = path | string | number | "true" | "false" | "null"
+//! ```
+
+use std::fmt;
+
+use regex::{Regex, RegexBuilder};
+use serde_json::Value;
+
+use super::expr::{CompareOp, Expr, Operand};
+use crate::record::FieldPath;
+
+/// A syntax error in a filter expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+  /// Offset of the offending token in characters.
+  pub pos: usize,
+  pub message: String,
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} at column {}", self.message, self.pos + 1)
+  }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn parse(input: &str) -> Result<Expr, ParseError> {
+  parse_tokens(input).map_err(|err| ParseError {
+    // Tokens are found by their byte offset.
+    pos: input[..err.pos].chars().count(),
+    ..err
+  })
+}
+
+fn parse_tokens(input: &str) -> Result<Expr, ParseError> {
+  let tokens = tokenize(input)?;
+  let mut parser = Parser {
+    tokens,
+    next: 0,
+    end: input.len(),
+  };
+  let expr = parser.expr()?;
+  match parser.peek() {
+    None => Ok(expr),
+    Some(_) => Err(parser.error("expected `&&`, `||` or end of filter")),
+  }
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+  Path(String),
+  Str(String),
+  Num(f64),
+  Regex(Regex),
+  True,
+  False,
+  Null,
+  LParen,
+  RParen,
+  And,
+  Or,
+  Not,
+  Compare(CompareOp),
+  Match,
+  NotMatch,
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+  let mut tokens = Vec::new();
+  let mut chars = input.char_indices().peekable();
+  while let Some(&(pos, c)) = chars.peek() {
+    let error = |message: &str| ParseError {
+      pos,
+      message: message.to_owned(),
+    };
+    let mut take = |token, len: usize| {
+      for _ in 0..len {
+        chars.next();
+      }
+      tokens.push((pos, token));
+    };
+    let rest = &input[pos..];
+    match c {
+      _ if c.is_whitespace() => {
+        chars.next();
+      }
+      '(' => take(Token::LParen, 1),
+      ')' => take(Token::RParen, 1),
+      '&' if rest.starts_with("&&") => take(Token::And, 2),
+      '|' if rest.starts_with("||") => take(Token::Or, 2),
+      '=' if rest.starts_with("==") => take(Token::Compare(CompareOp::Eq), 2),
+      '!' if rest.starts_with("!=") => take(Token::Compare(CompareOp::Ne), 2),
+      '!' if rest.starts_with("!~") => take(Token::NotMatch, 2),
+      '!' => take(Token::Not, 1),
+      '<' if rest.starts_with("<=") => take(Token::Compare(CompareOp::Le), 2),
+      '<' => take(Token::Compare(CompareOp::Lt), 1),
+      '>' if rest.starts_with(">=") => take(Token::Compare(CompareOp::Ge), 2),
+      '>' => take(Token::Compare(CompareOp::Gt), 1),
+      '~' => take(Token::Match, 1),
+      '"' | '\'' => {
+        chars.next();
+        let mut s = String::new();
+        loop {
+          match chars.next() {
+            Some((_, ch)) if ch == c => break,
+            Some((_, '\\')) => match chars.next() {
+              Some((_, 'n')) => s.push('\n'),
+              Some((_, 't')) => s.push('\t'),
+              Some((_, ch)) if ch == c || ch == '\\' => s.push(ch),
+              // Kept, so that strings work as regexes, as in `'a\.b'`.
+              Some((_, ch)) => {
+                s.push('\\');
+                s.push(ch);
+              }
+              None => return Err(error("unterminated string")),
+            },
+            Some((_, ch)) => s.push(ch),
+            None => return Err(error("unterminated string")),
+          }
+        }
+        tokens.push((pos, Token::Str(s)));
+      }
+      '/' => {
+        chars.next();
+        let mut pattern = String::new();
+        loop {
+          match chars.next() {
+            Some((_, '/')) => break,
+            Some((_, '\\')) => match chars.next() {
+              Some((_, '/')) => pattern.push('/'),
+              Some((_, ch)) => {
+                pattern.push('\\');
+                pattern.push(ch);
+              }
+              None => return Err(error("unterminated regex")),
+            },
+            Some((_, ch)) => pattern.push(ch),
+            None => return Err(error("unterminated regex")),
+          }
+        }
+        let mut builder = RegexBuilder::new(&pattern);
+        while let Some(&(_, flag)) = chars.peek() {
+          match flag {
+            'i' => builder.case_insensitive(true),
+            'm' => builder.multi_line(true),
+            's' => builder.dot_matches_new_line(true),
+            'x' => builder.ignore_whitespace(true),
+            _ if flag.is_alphanumeric() => return Err(error("unknown regex flag")),
+            _ => break,
+          };
+          chars.next();
+        }
+        let regex = builder.build().map_err(|err| error(&err.to_string()))?;
+        tokens.push((pos, Token::Regex(regex)));
+      }
+      '-' | '0'..='9' => {
+        let len = rest
+          .find(|ch: char| !(ch.is_ascii_alphanumeric() || matches!(ch, '.' | '-' | '+')))
+          .unwrap_or(rest.len());
+        let number = rest[..len].parse().map_err(|_| error("invalid number"))?;
+        take(Token::Num(number), rest[..len].chars().count());
+      }
+      _ if is_path_start(c) => {
+        let len = rest
+          .find(|ch: char| !(is_path_char(ch) || matches!(ch, '.' | '[' | ']')))
+          .unwrap_or(rest.len());
+        let word = &rest[..len];
+        let token = match word {
+          "true" => Token::True,
+          "false" => Token::False,
+          "null" => Token::Null,
+          _ => Token::Path(word.to_owned()),
+        };
+        take(token, word.chars().count());
+      }
+      _ => return Err(error(&format!("unexpected character `{c}`"))),
+    }
+  }
+  Ok(tokens)
+}
+
+fn is_path_start(c: char) -> bool {
+  c.is_alphabetic() || matches!(c, '_' | '$' | '@')
+}
+
+fn is_path_char(c: char) -> bool {
+  c.is_alphanumeric() || matches!(c, '_' | '$' | '@' | '-')
+}
+
+struct Parser {
+  tokens: Vec<(usize, Token)>,
+  next: usize,
+  /// Position reported for errors at the end of the input.
+  end: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.next).map(|(_, token)| token)
+  }
+
+  fn advance(&mut self) -> Option<Token> {
+    let token = self.tokens.get(self.next).map(|(_, token)| token.clone());
+    self.next += 1;
+    token
+  }
+
+  fn error(&self, message: &str) -> ParseError {
+    ParseError {
+      pos: self.tokens.get(self.next).map_or(self.end, |&(pos, _)| pos),
+      message: message.to_owned(),
+    }
+  }
+
+  fn expr(&mut self) -> Result<Expr, ParseError> {
+    let mut expr = self.and()?;
+    while matches!(self.peek(), Some(Token::Or)) {
+      self.advance();
+      expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+    }
+    Ok(expr)
+  }
+
+  fn and(&mut self) -> Result<Expr, ParseError> {
+    let mut expr = self.unary()?;
+    while matches!(self.peek(), Some(Token::And)) {
+      self.advance();
+      expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+    }
+    Ok(expr)
+  }
+
+  fn unary(&mut self) -> Result<Expr, ParseError> {
+    match self.peek() {
+      Some(Token::Not) => {
+        self.advance();
+        Ok(Expr::Not(Box::new(self.unary()?)))
+      }
+      Some(Token::LParen) => {
+        self.advance();
+        let expr = self.expr()?;
+        match self.peek() {
+          Some(Token::RParen) => {
+            self.advance();
+            Ok(expr)
+          }
+          _ => Err(self.error("expected `)`")),
+        }
+      }
+      _ => self.comparison(),
+    }
+  }
+
+  fn comparison(&mut self) -> Result<Expr, ParseError> {
+    let left = self.operand()?;
+    match self.peek() {
+      Some(&Token::Compare(op)) => {
+        self.advance();
+        Ok(Expr::Compare(left, op, self.operand()?))
+      }
+      Some(Token::Match) => {
+        self.advance();
+        Ok(Expr::Matches(left, self.pattern()?))
+      }
+      Some(Token::NotMatch) => {
+        self.advance();
+        Ok(Expr::Not(Box::new(Expr::Matches(left, self.pattern()?))))
+      }
+      _ => match left {
+        Operand::Field(path) => Ok(Expr::Exists(path)),
+        Operand::Literal(_) => Err(self.error("expected a comparison operator")),
+      },
+    }
+  }
+
+  fn operand(&mut self) -> Result<Operand, ParseError> {
+    let error = self.error("expected a field or a value");
+    let operand = match self.advance() {
+      Some(Token::Path(path)) => {
+        Operand::Field(FieldPath::parse(&path).ok_or_else(|| ParseError {
+          message: format!("invalid field path `{path}`"),
+          ..error
+        })?)
+      }
+      Some(Token::Str(s)) => Operand::Literal(Value::String(s)),
+      Some(Token::Num(n)) => Operand::Literal(n.into()),
+      Some(Token::True) => Operand::Literal(Value::Bool(true)),
+      Some(Token::False) => Operand::Literal(Value::Bool(false)),
+      Some(Token::Null) => Operand::Literal(Value::Null),
+      _ => return Err(error),
+    };
+    Ok(operand)
+  }
+
+  /// The right-hand side of `~`: a regex literal or a string used as one.
+  fn pattern(&mut self) -> Result<Regex, ParseError> {
+    let error = self.error("expected a /regex/ or a string");
+    match self.advance() {
+      Some(Token::Regex(regex)) => Ok(regex),
+      Some(Token::Str(s)) => Regex::new(&s).map_err(|err| ParseError {
+        message: err.to_string(),
+        ..error
+      }),
+      _ => Err(error),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::record::Record;
+
+  fn eval(filter: &str, line: &str) -> bool {
+    let expr = parse(filter).unwrap();
+    expr.eval(&Record::parse(line.as_bytes()).unwrap())
+  }
+
+  fn error(filter: &str) -> String {
+    parse(filter).unwrap_err().to_string()
+  }
+
+  #[test]
+  fn and_binds_tighter_than_or() {
+    let expr = parse("a || b && c").unwrap();
+    let Expr::Or(left, right) = expr else {
+      panic!("expected `||` at the top: {expr:?}");
+    };
+    assert!(matches!(*left, Expr::Exists(_)));
+    assert!(matches!(*right, Expr::And(..)));
+
+    assert!(eval("a || b && c", r#"{"a":1}"#));
+    assert!(!eval("a || b && c", r#"{"b":1}"#));
+    assert!(!eval("(a || b) && c", r#"{"a":1}"#));
+    assert!(eval("!a && b", r#"{"b":1}"#));
+  }
+
+  #[test]
+  fn not_match() {
+    let line = r#"{"message":"connect timeout"}"#;
+    assert!(eval("message ~ /timeout/", line));
+    assert!(!eval("message !~ /timeout/", line));
+    assert!(eval("message !~ 'refused'", line));
+    assert!(eval("missing !~ /x/", line));
+    assert!(matches!(
+      parse("message !~ /x/").unwrap(),
+      Expr::Not(inner) if matches!(*inner, Expr::Matches(..))
+    ));
+  }
+
+  #[test]
+  fn regex_flags() {
+    let line = r#"{"message":"first\nSecond line"}"#;
+    assert!(!eval("message ~ /second/", line));
+    assert!(eval("message ~ /second/i", line));
+    assert!(!eval("message ~ /^Second/", line));
+    assert!(eval("message ~ /^Second/m", line));
+    assert!(!eval("message ~ /first.Second/", line));
+    assert!(eval("message ~ /first.Second/s", line));
+    assert!(eval("message ~ /f i r s t/x", line));
+    assert!(eval("message ~ /SECOND/im && message ~ /^s/im", line));
+    assert!(eval(r#"message ~ /a\/b/"#, r#"{"message":"a/b"}"#));
+  }
+
+  #[test]
+  fn nested_and_indexed_paths() {
+    let line = r#"{"meta":{"req":{"url":"/health","headers":[{"name":"host"}]}},"tags":["a","b"]}"#;
+    assert!(eval("meta.req.url == '/health'", line));
+    assert!(eval("tags[1] == 'b'", line));
+    assert!(eval("tags.0 == 'a'", line));
+    assert!(eval("meta.req.headers[0].name == 'host'", line));
+    assert!(!eval("tags[2]", line));
+    assert!(!eval("meta.res.status", line));
+    assert_eq!(
+      error("tags[x] == 1"),
+      "invalid field path `tags[x]` at column 1"
+    );
+  }
+
+  #[test]
+  fn error_columns() {
+    assert_eq!(error("a = 1"), "unexpected character `=` at column 3");
+    assert_eq!(error("a == "), "expected a field or a value at column 6");
+    assert_eq!(error("(a || b"), "expected `)` at column 8");
+    assert_eq!(
+      error("a b"),
+      "expected `&&`, `||` or end of filter at column 3"
+    );
+    assert_eq!(
+      error("1 && a"),
+      "expected a comparison operator at column 3"
+    );
+    assert_eq!(error("a ~ 1"), "expected a /regex/ or a string at column 5");
+    assert_eq!(error("a ~ /x/q"), "unknown regex flag at column 5");
+    assert_eq!(error("a == 'open"), "unterminated string at column 6");
+    assert_eq!(error("a ~ /open"), "unterminated regex at column 5");
+    assert_eq!(error("a > 1.2.3"), "invalid number at column 5");
+    assert_eq!(
+      error("message == 'héllo' &&"),
+      "expected a field or a value at column 22"
+    );
+    assert_eq!(error("名前 = 1"), "unexpected character `=` at column 4");
+  }
+
+  #[test]
+  fn string_escapes() {
+    assert!(eval(r"message == 'it\'s'", r#"{"message":"it's"}"#));
+    assert!(eval(
+      r#"message == "say \"hi\"""#,
+      r#"{"message":"say \"hi\""}"#
+    ));
+    assert!(eval(r"message == 'a\nb\tc'", r#"{"message":"a\nb\tc"}"#));
+    assert!(eval(r"message == 'C:\\temp'", r#"{"message":"C:\\temp"}"#));
+    // Other escapes are kept for the regex.
+    assert!(eval(r"message ~ 'a\.b'", r#"{"message":"a.b"}"#));
+    assert!(!eval(r"message ~ 'a\.b'", r#"{"message":"axb"}"#));
+    assert!(eval(r"message !~ 'a\.b'", r#"{"message":"axb"}"#));
+    assert!(eval(r"message ~ '\d+ ms'", r#"{"message":"took 12 ms"}"#));
+    assert!(eval(r"message == 'a\qb'", r#"{"message":"a\\qb"}"#));
+  }
+}
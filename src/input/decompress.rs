@@ -0,0 +1,134 @@
+//! Transparent decompression of rotated log archives, such as the `.gz` files
+//! written by `winston-daily-rotate-file` with `zippedArchive: true`.
+
+use std::io::{self, BufRead, BufReader, Read};
+
+use flate2::bufread::MultiGzDecoder;
+use ruzstd::decoding::{FrameDecoder, StreamingDecoder};
+
+use super::BUFFER_SIZE;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Wraps `input` in a decoder if it starts with gzip or zstd magic bytes, and
+/// returns it unchanged otherwise.
+pub fn decompress(mut input: Box<dyn BufRead>) -> io::Result<Box<dyn BufRead>> {
+  let head = input.fill_buf()?;
+  if head.starts_with(&GZIP_MAGIC) {
+    Ok(Box::new(BufReader::with_capacity(
+      BUFFER_SIZE,
+      MultiGzDecoder::new(input),
+    )))
+  } else if head.starts_with(&ZSTD_MAGIC) {
+    Ok(Box::new(BufReader::with_capacity(
+      BUFFER_SIZE,
+      ZstdDecoder::new(input),
+    )))
+  } else {
+    Ok(input)
+  }
+}
+
+/// Whether `head`, the start of a file, has gzip or zstd magic bytes.
+pub fn is_compressed(head: &[u8]) -> bool {
+  head.starts_with(&GZIP_MAGIC) || head.starts_with(&ZSTD_MAGIC)
+}
+
+/// Decodes a zstd stream made of any number of concatenated frames.
+struct ZstdDecoder {
+  /// The decoder for the current frame, if one has been started.
+  frame: Option<StreamingDecoder<Box<dyn BufRead>, FrameDecoder>>,
+  /// The compressed input between frames.
+  source: Option<Box<dyn BufRead>>,
+}
+
+impl ZstdDecoder {
+  fn new(source: Box<dyn BufRead>) -> Self {
+    ZstdDecoder {
+      frame: None,
+      source: Some(source),
+    }
+  }
+}
+
+impl Read for ZstdDecoder {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+      if let Some(frame) = &mut self.frame {
+        let n = frame.read(buf)?;
+        if n > 0 || buf.is_empty() {
+          return Ok(n);
+        }
+        self.source = self.frame.take().map(StreamingDecoder::into_inner);
+      }
+      let Some(mut source) = self.source.take() else {
+        return Ok(0);
+      };
+      if source.fill_buf()?.is_empty() {
+        return Ok(0);
+      }
+      let frame = StreamingDecoder::new(source)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+      self.frame = Some(frame);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Write;
+
+  use flate2::{write::GzEncoder, Compression};
+  use ruzstd::encoding::{compress_to_vec, CompressionLevel};
+
+  use super::*;
+
+  const PARTS: [&str; 3] = [
+    "{\"level\":\"info\",\"message\":\"first\"}\n",
+    "{\"level\":\"warn\",\"message\":\"second\"}\n",
+    "{\"level\":\"error\",\"message\":\"third\"}\n",
+  ];
+
+  /// Decompresses `data`, read a few bytes at a time.
+  fn read_all(data: Vec<u8>) -> String {
+    let input = Box::new(BufReader::with_capacity(7, io::Cursor::new(data)));
+    let mut text = String::new();
+    decompress(input)
+      .unwrap()
+      .read_to_string(&mut text)
+      .unwrap();
+    text
+  }
+
+  #[test]
+  fn concatenated_zstd_frames() {
+    let data: Vec<u8> = PARTS
+      .iter()
+      .flat_map(|part| compress_to_vec(part.as_bytes(), CompressionLevel::Fastest))
+      .collect();
+    assert!(is_compressed(&data));
+    assert_eq!(read_all(data), PARTS.concat());
+  }
+
+  #[test]
+  fn gzip_members() {
+    let data: Vec<u8> = PARTS
+      .iter()
+      .flat_map(|part| {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(part.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+      })
+      .collect();
+    assert!(is_compressed(&data));
+    assert_eq!(read_all(data), PARTS.concat());
+  }
+
+  #[test]
+  fn plain_text_is_passed_through() {
+    let data = PARTS.concat().into_bytes();
+    assert!(!is_compressed(&data));
+    assert_eq!(read_all(data), PARTS.concat());
+  }
+}
@@ -0,0 +1,430 @@
+//! Unwrapping the envelopes that log drivers put around each line, so that
+//! `/var/lib/docker/containers/*/*-json.log` and the CRI log files of
+//! Kubernetes can be read directly:
+//!
+//! ```text
+//! {"log":"{\"level\":\"info\",...}\n","stream":"stdout","time":"2026-10-16T10:00:00.1Z"}
+//! 2026-10-16T10:00:00.1Z stdout F {"level":"info",...}
+//! ```
+//!
+//! Both split lines longer than 16KB into several entries, which are joined
+//! again. A JSON line is passed on behind the CRI prefix, which
+//! [`prefix`](crate::prefix) turns into the `stream` and `timestamp` fields;
+//! any other line is passed on as it is.
+
+use std::{
+  borrow::Cow,
+  io::{self, BufRead, Read},
+};
+
+use serde::Deserialize;
+
+use super::{trim_newline, LineSink, LineSplitter};
+
+/// An entry of Docker's `json-file` log driver.
+#[derive(Deserialize)]
+struct DockerEntry {
+  log: String,
+  stream: Option<String>,
+  time: Option<String>,
+}
+
+/// A line with the parts of its envelope.
+struct Unwrapped<'a> {
+  time: Cow<'a, [u8]>,
+  stream: Cow<'a, [u8]>,
+  content: Cow<'a, [u8]>,
+  /// Whether the line continues in the next entry.
+  partial: bool,
+}
+
+/// Takes envelopes off lines and joins partial lines, for a single input.
+#[derive(Debug, Default)]
+pub struct Unwrapper {
+  /// The beginning of a line that was split across entries.
+  partial: Vec<u8>,
+}
+
+impl Unwrapper {
+  pub fn new() -> Self {
+    Unwrapper::default()
+  }
+
+  /// Unwraps `line`, without its line terminator. Returns the line to pass on,
+  /// or `None` if `line` only is the beginning of a longer one.
+  pub fn unwrap<'a>(&'a mut self, line: &'a [u8]) -> Option<Cow<'a, [u8]>> {
+    let Some(entry) = unwrap_docker(line).or_else(|| unwrap_cri(line)) else {
+      // Not an envelope after all; whatever was pending will not be completed.
+      self.partial.clear();
+      return Some(Cow::Borrowed(line));
+    };
+    if entry.partial {
+      self.partial.extend_from_slice(&entry.content);
+      return None;
+    }
+    let content = if self.partial.is_empty() {
+      entry.content
+    } else {
+      self.partial.extend_from_slice(&entry.content);
+      Cow::Owned(std::mem::take(&mut self.partial))
+    };
+    if !content.trim_ascii_start().starts_with(b"{") || entry.time.is_empty() {
+      return Some(content);
+    }
+    let mut line = Vec::with_capacity(entry.time.len() + entry.stream.len() + content.len() + 4);
+    line.extend_from_slice(&entry.time);
+    line.push(b' ');
+    line.extend_from_slice(&entry.stream);
+    line.extend_from_slice(b" F ");
+    line.extend_from_slice(&content);
+    Some(Cow::Owned(line))
+  }
+
+  /// Returns the beginning of a line whose end never came.
+  pub fn finish(&mut self) -> Option<Vec<u8>> {
+    (!self.partial.is_empty()).then(|| std::mem::take(&mut self.partial))
+  }
+}
+
+/// Whether `line` is wrapped in an envelope of a known log driver.
+pub fn is_envelope(line: &[u8]) -> bool {
+  line.starts_with(br#"{"log":"#) || unwrap_cri(line).is_some()
+}
+
+fn unwrap_docker(line: &[u8]) -> Option<Unwrapped<'_>> {
+  if !line.starts_with(br#"{"log":"#) {
+    return None;
+  }
+  let entry: DockerEntry = serde_json::from_slice(line).ok()?;
+  let partial = !entry.log.ends_with('\n');
+  let content = trim_newline(entry.log.as_bytes()).to_vec();
+  Some(Unwrapped {
+    time: Cow::Owned(entry.time.unwrap_or_default().into_bytes()),
+    stream: Cow::Owned(
+      entry
+        .stream
+        .unwrap_or_else(|| "stdout".to_owned())
+        .into_bytes(),
+    ),
+    content: Cow::Owned(content),
+    partial,
+  })
+}
+
+/// Parses `TIME STREAM TAG CONTENT`, where the tag is `P` for a partial line
+/// and `F` for the full line or its last part.
+fn unwrap_cri(line: &[u8]) -> Option<Unwrapped<'_>> {
+  let mut parts = line.splitn(4, |&b| b == b' ');
+  let time = parts.next()?;
+  let is_time = time.len() >= 20
+    && time[..4].iter().all(u8::is_ascii_digit)
+    && time[4] == b'-'
+    && time[10] == b'T';
+  if !is_time {
+    return None;
+  }
+  let stream = parts
+    .next()
+    .filter(|stream| matches!(*stream, b"stdout" | b"stderr"))?;
+  let partial = match parts.next()? {
+    b"P" => true,
+    b"F" => false,
+    _ => return None,
+  };
+  Some(Unwrapped {
+    time: Cow::Borrowed(time),
+    stream: Cow::Borrowed(stream),
+    content: Cow::Borrowed(parts.next().unwrap_or_default()),
+    partial,
+  })
+}
+
+/// Unwraps the lines of `reader` if its first line is in an envelope, and
+/// leaves it alone otherwise.
+pub fn unwrap_envelopes(mut reader: Box<dyn BufRead>) -> io::Result<Box<dyn BufRead>> {
+  let head = reader.fill_buf()?;
+  let first = head.split(|&b| b == b'\n').next().unwrap_or_default();
+  if is_envelope(trim_newline(first)) {
+    Ok(Box::new(UnwrapReader {
+      inner: reader,
+      lines: LineSplitter::new(0),
+      unwrapper: Unwrapper::new(),
+      out: Vec::new(),
+      pos: 0,
+    }))
+  } else {
+    Ok(reader)
+  }
+}
+
+/// Reads the unwrapped lines of an input.
+struct UnwrapReader<R> {
+  inner: R,
+  lines: LineSplitter,
+  unwrapper: Unwrapper,
+  /// Unwrapped lines that have not been consumed yet, from `pos` on.
+  out: Vec<u8>,
+  pos: usize,
+}
+
+impl<R: BufRead> Read for UnwrapReader<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let available = self.fill_buf()?;
+    let n = available.len().min(buf.len());
+    buf[..n].copy_from_slice(&available[..n]);
+    self.consume(n);
+    Ok(n)
+  }
+}
+
+impl<R: BufRead> BufRead for UnwrapReader<R> {
+  /// Unwraps all lines that the inner reader has buffered at once.
+  fn fill_buf(&mut self) -> io::Result<&[u8]> {
+    while self.pos == self.out.len() {
+      self.out.clear();
+      self.pos = 0;
+      let mut sink = UnwrapSink {
+        unwrapper: &mut self.unwrapper,
+        sink: &mut Collect(&mut self.out),
+      };
+      let buf = self.inner.fill_buf()?;
+      if buf.is_empty() {
+        self.lines.finish(&mut sink)?;
+        if let Some(rest) = self.unwrapper.finish() {
+          Collect(&mut self.out).line(0, &rest)?;
+        }
+        break;
+      }
+      let len = buf.len();
+      self.lines.push(buf, &mut sink)?;
+      self.inner.consume(len);
+    }
+    Ok(&self.out[self.pos..])
+  }
+
+  fn consume(&mut self, amt: usize) {
+    self.pos = (self.pos + amt).min(self.out.len());
+  }
+}
+
+/// Collects lines into a buffer, each followed by a newline.
+struct Collect<'a>(&'a mut Vec<u8>);
+
+impl LineSink for Collect<'_> {
+  fn line(&mut self, _source: usize, line: &[u8]) -> io::Result<()> {
+    self.0.extend_from_slice(line);
+    self.0.push(b'\n');
+    Ok(())
+  }
+}
+
+/// Unwraps the lines of a single input on their way to `sink`.
+pub struct UnwrapSink<'a, S> {
+  pub unwrapper: &'a mut Unwrapper,
+  pub sink: &'a mut S,
+}
+
+impl<S: LineSink> LineSink for UnwrapSink<'_, S> {
+  fn line(&mut self, source: usize, line: &[u8]) -> io::Result<()> {
+    match self.unwrapper.unwrap(line) {
+      Some(line) => self.sink.line(source, &line),
+      None => Ok(()),
+    }
+  }
+
+  fn idle(&mut self) -> io::Result<()> {
+    self.sink.idle()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    filter::Filter,
+    input::read_lines,
+    level::Levels,
+    output::{Encoder, OutputFormat},
+    printer::Printer,
+    render::Renderer,
+    template::Template,
+  };
+
+  const TIME: &str = "2026-10-16T10:00:00.1Z";
+
+  /// Feeds `lines` to a new [`Unwrapper`] and collects what it passes on,
+  /// followed by what [`Unwrapper::finish`] returns.
+  fn unwrap_all(lines: &[&str]) -> Vec<String> {
+    let mut unwrapper = Unwrapper::new();
+    let mut out: Vec<String> = lines
+      .iter()
+      .filter_map(|line| {
+        unwrapper
+          .unwrap(line.as_bytes())
+          .map(|line| line.into_owned())
+      })
+      .map(|line| String::from_utf8(line).unwrap())
+      .collect();
+    out.extend(
+      unwrapper
+        .finish()
+        .map(|line| String::from_utf8(line).unwrap()),
+    );
+    out
+  }
+
+  fn docker(log: &str) -> String {
+    serde_json::json!({"log": log, "stream": "stderr", "time": TIME}).to_string()
+  }
+
+  #[test]
+  fn docker_lines() {
+    assert_eq!(
+      unwrap_all(&[
+        &docker("{\"level\":\"info\",\"message\":\"hi\"}\n"),
+        &docker("Listening on port 3000\n"),
+      ]),
+      [
+        format!(r#"{TIME} stderr F {{"level":"info","message":"hi"}}"#),
+        "Listening on port 3000".to_owned(),
+      ]
+    );
+  }
+
+  #[test]
+  fn docker_partial_lines_are_joined() {
+    assert_eq!(
+      unwrap_all(&[
+        &docker("{\"level\":\"info\","),
+        &docker("\"message\":\"a long line\""),
+        &docker("}\n"),
+        &docker("next\n"),
+      ]),
+      [
+        format!(r#"{TIME} stderr F {{"level":"info","message":"a long line"}}"#),
+        "next".to_owned(),
+      ]
+    );
+  }
+
+  #[test]
+  fn cri_partial_lines_are_joined() {
+    assert_eq!(
+      unwrap_all(&[
+        &format!(r#"{TIME} stdout P {{"level":"info","#),
+        &format!(r#"{TIME} stdout P "message":"#),
+        &format!(r#"{TIME} stdout F "joined"}}"#),
+        &format!("{TIME} stdout F plain"),
+        &format!("{TIME} stderr P par"),
+        &format!("{TIME} stderr F tial"),
+        &format!("{TIME} stdout F "),
+      ]),
+      [
+        format!(r#"{TIME} stdout F {{"level":"info","message":"joined"}}"#),
+        "plain".to_owned(),
+        "partial".to_owned(),
+        String::new(),
+      ]
+    );
+  }
+
+  #[test]
+  fn other_lines_drop_a_pending_partial() {
+    assert_eq!(
+      unwrap_all(&[
+        &format!("{TIME} stdout P lost "),
+        "not in an envelope",
+        &format!("{TIME} stdout F end"),
+        &docker("lost too "),
+        "{\"level\":\"info\"}",
+        &docker("end\n"),
+      ]),
+      ["not in an envelope", "end", "{\"level\":\"info\"}", "end"]
+    );
+  }
+
+  #[test]
+  fn finish_returns_an_unfinished_line() {
+    assert_eq!(
+      unwrap_all(&[
+        &format!("{TIME} stdout F done"),
+        &format!("{TIME} stdout P cut")
+      ]),
+      ["done", "cut"]
+    );
+    let mut unwrapper = Unwrapper::new();
+    assert_eq!(unwrapper.finish(), None);
+  }
+
+  #[test]
+  fn reader_unwraps_to_the_end() {
+    let input = format!(
+      "{}\n{}\n{}",
+      docker("{\"message\":\"hi\"}\n"),
+      docker("cut "),
+      docker("off")
+    );
+    let reader: Box<dyn BufRead> = Box::new(io::Cursor::new(input.into_bytes()));
+    let mut out = String::new();
+    unwrap_envelopes(reader)
+      .unwrap()
+      .read_to_string(&mut out)
+      .unwrap();
+    assert_eq!(
+      out,
+      format!("{TIME} stderr F {{\"message\":\"hi\"}}\ncut off\n")
+    );
+
+    let reader: Box<dyn BufRead> = Box::new(&b"plain\n{\"log\":\"x\\n\"}\n"[..]);
+    let mut out = String::new();
+    unwrap_envelopes(reader)
+      .unwrap()
+      .read_to_string(&mut out)
+      .unwrap();
+    assert_eq!(out, "plain\n{\"log\":\"x\\n\"}\n");
+  }
+
+  #[test]
+  fn reader_unwraps_all_buffered_lines_at_once() {
+    let input = format!("{}\n{}\n{}\n", docker("a\n"), docker("b\n"), docker("c\n"));
+    let reader: Box<dyn BufRead> = Box::new(io::Cursor::new(input.into_bytes()));
+    let mut reader = unwrap_envelopes(reader).unwrap();
+    assert_eq!(reader.fill_buf().unwrap(), b"a\nb\nc\n");
+  }
+
+  #[test]
+  fn continuations_are_joined_across_entries() {
+    let input = [
+      docker("{\"level\":\"error\",\"message\":\"request failed\"}\n"),
+      docker("Error: connect ECONNREFUSED 127.0.0.1:5432\n"),
+      docker("    at TCPConnectWrap.afterConnect (node:net:1555:16)\n"),
+      docker("{\"level\":\"info\",\"message\":\"next\"}\n"),
+    ]
+    .join("\n");
+    // Small buffers, so that the entries are unwrapped over several reads.
+    let reader: Box<dyn BufRead> = Box::new(io::BufReader::with_capacity(
+      64,
+      io::Cursor::new(input.into_bytes()),
+    ));
+    let filter = Filter::new().expr("message ~ /ECONNREFUSED/").unwrap();
+    let renderer = Renderer::new(Levels::npm(), Template::default());
+    let mut out = Vec::new();
+    let mut printer = Printer::new(&mut out, filter, renderer)
+      .encoder(Encoder::new(OutputFormat::Ndjson))
+      .join_continuations(true);
+    read_lines(unwrap_envelopes(reader).unwrap(), 0, &mut printer, || false).unwrap();
+    printer.flush().unwrap();
+    drop(printer);
+    let records: Vec<serde_json::Value> = serde_json::Deserializer::from_slice(&out)
+      .into_iter()
+      .collect::<Result<_, _>>()
+      .unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["level"], "error");
+    assert_eq!(records[0]["stream"], "stderr");
+    assert_eq!(
+      records[0]["message"],
+      "request failed\nError: connect ECONNREFUSED 127.0.0.1:5432\n    at TCPConnectWrap.afterConnect (node:net:1555:16)"
+    );
+  }
+}
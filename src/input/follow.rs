@@ -0,0 +1,217 @@
+//! `tail -F`-style following of log files.
+
+use std::{
+  fs::{self, File, Metadata},
+  io::{self, Read, Seek, SeekFrom},
+  path::{Path, PathBuf},
+  thread,
+  time::Duration,
+};
+
+use super::{
+  envelope::{UnwrapSink, Unwrapper},
+  LineSink, LineSplitter, BUFFER_SIZE,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Feeds lines appended to the files at `paths` to `sink`, forever.
+///
+/// Like `tail -F`, a file is reopened when it is replaced (as
+/// `winston-daily-rotate-file` and `logrotate` do) or read again from the
+/// start when it is truncated, and files that do not exist yet are waited
+/// for. Only lines written after the call are shown.
+pub fn follow<S: LineSink>(paths: &[PathBuf], sink: &mut S) -> io::Result<()> {
+  let mut files: Vec<Followed> = paths
+    .iter()
+    .enumerate()
+    .map(|(source, path)| Followed::open(source, path))
+    .collect();
+  let mut buf = vec![0; BUFFER_SIZE];
+  loop {
+    let mut active = false;
+    for file in &mut files {
+      active |= file.poll(&mut buf, sink)?;
+    }
+    if !active {
+      sink.idle()?;
+      thread::sleep(POLL_INTERVAL);
+    }
+  }
+}
+
+struct Followed {
+  path: PathBuf,
+  file: Option<File>,
+  id: Option<FileId>,
+  /// How much of the current file has been read.
+  pos: u64,
+  lines: LineSplitter,
+  envelopes: Unwrapper,
+}
+
+impl Followed {
+  fn open(source: usize, path: &Path) -> Self {
+    let mut followed = Followed {
+      path: path.to_owned(),
+      file: None,
+      id: None,
+      pos: 0,
+      lines: LineSplitter::new(source),
+      envelopes: Unwrapper::new(),
+    };
+    match File::open(path).and_then(|mut file| Ok((file.seek(SeekFrom::End(0))?, file))) {
+      Ok((pos, file)) => {
+        followed.id = file.metadata().ok().as_ref().and_then(file_id);
+        followed.file = Some(file);
+        followed.pos = pos;
+      }
+      Err(err) => warn(path, &format!("{err}; waiting for it to appear")),
+    }
+    followed
+  }
+
+  /// Reads whatever has been appended since the last call and deals with
+  /// rotation. Returns whether anything happened that warrants polling again
+  /// right away.
+  fn poll<S: LineSink>(&mut self, buf: &mut [u8], sink: &mut S) -> io::Result<bool> {
+    let mut active = false;
+    loop {
+      let Some(file) = &mut self.file else {
+        return Ok(self.reopen());
+      };
+      let n = file.read(buf)?;
+      if n == 0 {
+        break;
+      }
+      active = true;
+      self.pos += n as u64;
+      let mut sink = UnwrapSink {
+        unwrapper: &mut self.envelopes,
+        sink,
+      };
+      self.lines.push(&buf[..n], &mut sink)?;
+    }
+
+    // The current file has been drained, check whether it is still the one at
+    // `path`.
+    let Ok(meta) = fs::metadata(&self.path) else {
+      // Removed, but a new file may still appear.
+      return Ok(active);
+    };
+    match (file_id(&meta), self.id) {
+      (Some(new), Some(old)) if new != old => {
+        let mut sink = UnwrapSink {
+          unwrapper: &mut self.envelopes,
+          sink,
+        };
+        self.lines.finish(&mut sink)?;
+        self.envelopes = Unwrapper::new();
+        warn(&self.path, "file has been replaced; following new file");
+        self.file = None;
+        return Ok(self.reopen() || active);
+      }
+      _ if meta.len() < self.pos => {
+        warn(&self.path, "file truncated");
+        if let Some(file) = &mut self.file {
+          file.seek(SeekFrom::Start(0))?;
+        }
+        self.pos = 0;
+        self.lines.clear();
+        self.envelopes = Unwrapper::new();
+        return Ok(true);
+      }
+      _ => {}
+    }
+    Ok(active)
+  }
+
+  /// Opens a file that has (re)appeared at `path`, reading it from the start.
+  fn reopen(&mut self) -> bool {
+    match File::open(&self.path) {
+      Ok(file) => {
+        self.id = file.metadata().ok().as_ref().and_then(file_id);
+        self.file = Some(file);
+        self.pos = 0;
+        true
+      }
+      Err(_) => false,
+    }
+  }
+}
+
+fn warn(path: &Path, message: &str) {
+  eprintln!("winstonjson: {}: {message}", path.display());
+}
+
+type FileId = (u64, u64);
+
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> Option<FileId> {
+  use std::os::unix::fs::MetadataExt;
+  Some((meta.dev(), meta.ino()))
+}
+
+/// Without inode numbers, rotation can only be noticed as truncation.
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> Option<FileId> {
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Write;
+
+  use super::*;
+
+  /// Collects the lines it is fed.
+  #[derive(Default)]
+  struct Lines(Vec<String>);
+
+  impl LineSink for Lines {
+    fn line(&mut self, _source: usize, line: &[u8]) -> io::Result<()> {
+      self.0.push(String::from_utf8_lossy(line).into_owned());
+      Ok(())
+    }
+  }
+
+  fn append(path: &Path, text: &str) {
+    let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+    file.write_all(text.as_bytes()).unwrap();
+  }
+
+  /// Polls `followed` until there is nothing left to do.
+  fn drain(followed: &mut Followed) -> Vec<String> {
+    let mut buf = vec![0; 16];
+    let mut lines = Lines::default();
+    while followed.poll(&mut buf, &mut lines).unwrap() {}
+    lines.0
+  }
+
+  #[test]
+  fn truncated_and_replaced_files_are_read_again() {
+    let dir = std::env::temp_dir().join(format!("winstonjson-follow-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("app.log");
+    fs::write(&path, "before the start\n").unwrap();
+
+    let mut followed = Followed::open(0, &path);
+    assert_eq!(drain(&mut followed), Vec::<String>::new());
+    append(&path, "one\ntw");
+    assert_eq!(drain(&mut followed), ["one"]);
+
+    // Truncating drops the partial line and starts over.
+    fs::write(&path, "two\n").unwrap();
+    assert_eq!(drain(&mut followed), ["two"]);
+
+    // Rotation moves the file away and creates a new one in its place.
+    fs::rename(&path, dir.join("app.log.1")).unwrap();
+    append(&dir.join("app.log.1"), "three\n");
+    fs::write(&path, "four\n").unwrap();
+    assert_eq!(drain(&mut followed), ["three", "four"]);
+    append(&path, "five\n");
+    assert_eq!(drain(&mut followed), ["five"]);
+
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
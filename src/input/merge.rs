@@ -0,0 +1,170 @@
+//! Interleaving several logs in timestamp order.
+
+use std::{cmp::Reverse, collections::BinaryHeap, io::BufRead};
+
+use jiff::Timestamp;
+
+use super::{line_timestamp, trim_newline, Input, LineSink, OpenOptions};
+
+/// Feeds the lines of all `inputs` to `sink`, ordered by the records'
+/// timestamps.
+///
+/// Each input is assumed to be sorted already, so this is a k-way merge that
+/// only holds one line per input in memory. Lines without a timestamp, such as
+/// non-JSON output, stay right after the line that preceded them in their own
+/// input. Lines with equal timestamps are taken from the inputs in order.
+pub fn merge<S: LineSink>(
+  inputs: &[Input],
+  options: &OpenOptions,
+  sink: &mut S,
+) -> anyhow::Result<()> {
+  let mut readers = Vec::with_capacity(inputs.len());
+  let mut heap = BinaryHeap::with_capacity(inputs.len());
+  for (source, input) in inputs.iter().enumerate() {
+    let mut reader = Reader {
+      input: input.open(options)?,
+      line: Vec::new(),
+      key: None,
+      drained: false,
+    };
+    if reader.advance()? {
+      heap.push(Reverse((reader.key, source)));
+    }
+    readers.push(reader);
+  }
+
+  while let Some(Reverse((_, source))) = heap.pop() {
+    let reader = &mut readers[source];
+    sink.line(source, trim_newline(&reader.line))?;
+    if reader.drained && inputs[source].is_stalled() {
+      // Reading on would block, so show what is ready.
+      sink.idle()?;
+    }
+    if reader.advance()? {
+      heap.push(Reverse((reader.key, source)));
+    }
+  }
+  sink.idle()?;
+  Ok(())
+}
+
+struct Reader {
+  input: Box<dyn BufRead>,
+  /// The line that is next in this input.
+  line: Vec<u8>,
+  /// The timestamp `line` is sorted by.
+  key: Option<Timestamp>,
+  /// Whether reading `line` took all that `input` had buffered.
+  drained: bool,
+}
+
+impl Reader {
+  /// Reads the next line, returning `false` at the end of the input.
+  fn advance(&mut self) -> std::io::Result<bool> {
+    self.line.clear();
+    loop {
+      let buf = self.input.fill_buf()?;
+      if buf.is_empty() {
+        break;
+      }
+      let end = memchr::memchr(b'\n', buf);
+      let len = end.map_or(buf.len(), |end| end + 1);
+      self.line.extend_from_slice(&buf[..len]);
+      self.drained = len == buf.len();
+      self.input.consume(len);
+      if end.is_some() {
+        break;
+      }
+    }
+    if self.line.is_empty() {
+      return Ok(false);
+    }
+    if let Some(timestamp) = line_timestamp(&self.line) {
+      self.key = Some(timestamp);
+    }
+    Ok(true)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, io};
+
+  use super::*;
+
+  /// Collects the lines it is fed, with the input they came from.
+  #[derive(Default)]
+  struct Lines(Vec<(usize, String)>);
+
+  impl LineSink for Lines {
+    fn line(&mut self, source: usize, line: &[u8]) -> io::Result<()> {
+      self
+        .0
+        .push((source, String::from_utf8_lossy(line).into_owned()));
+      Ok(())
+    }
+  }
+
+  fn record(second: u32, message: &str) -> String {
+    format!(r#"{{"timestamp":"2026-10-16T10:00:{second:02}Z","message":"{message}"}}"#)
+  }
+
+  #[test]
+  fn ties_are_taken_from_the_inputs_in_order() {
+    let dir = std::env::temp_dir().join(format!("winstonjson-merge-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let first = dir.join("api.log");
+    let second = dir.join("worker.log");
+    fs::write(
+      &first,
+      [
+        record(1, "api 1"),
+        record(2, "api 2"),
+        "  continued".to_owned(),
+        record(3, "api 3"),
+      ]
+      .join("\n"),
+    )
+    .unwrap();
+    fs::write(
+      &second,
+      [
+        record(0, "worker 0"),
+        record(2, "worker 2"),
+        record(2, "worker 2 again"),
+        record(3, "worker 3"),
+      ]
+      .join("\n")
+        + "\n",
+    )
+    .unwrap();
+
+    let mut lines = Lines::default();
+    merge(
+      &[Input::File(first), Input::File(second)],
+      &OpenOptions::default(),
+      &mut lines,
+    )
+    .unwrap();
+    let merged: Vec<_> = lines
+      .0
+      .iter()
+      .map(|(source, line)| (*source, line.rsplit('"').nth(1).unwrap_or(line)))
+      .collect();
+    assert_eq!(
+      merged,
+      [
+        (1, "worker 0"),
+        (0, "api 1"),
+        (0, "api 2"),
+        (0, "  continued"),
+        (1, "worker 2"),
+        (1, "worker 2 again"),
+        (0, "api 3"),
+        (1, "worker 3"),
+      ]
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
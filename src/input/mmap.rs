@@ -0,0 +1,87 @@
+//! Reading regular files through a memory mapping, so that lines are handed
+//! out straight from the page cache instead of being copied into buffers.
+
+use std::{
+  fs::File,
+  io::{self, BufRead, Read},
+  time::Duration,
+};
+
+use memmap2::Mmap;
+
+/// Files modified more recently than this are taken to be still written to.
+const SETTLED: Duration = Duration::from_secs(60);
+
+/// A file mapped into memory, read from front to back. All of the rest of
+/// the file is available as the buffer at once.
+#[derive(Debug)]
+pub struct Mapped {
+  map: Mmap,
+  pos: usize,
+}
+
+impl Mapped {
+  /// Maps `file` if it is a regular file, and returns `None` for empty files,
+  /// pipes, sockets and other files that have to be read instead.
+  ///
+  /// Files that were modified within the last minute are not mapped either:
+  /// they are likely still being written to, and log rotation with
+  /// `copytruncate` may truncate them at any time.
+  pub fn new(file: &File) -> io::Result<Option<Mapped>> {
+    let metadata = file.metadata()?;
+    if !metadata.is_file() || metadata.len() == 0 {
+      return Ok(None);
+    }
+    // A modification time in the future counts as recent.
+    let recent = metadata
+      .modified()
+      .is_ok_and(|modified| modified.elapsed().map_or(true, |age| age < SETTLED));
+    if recent {
+      return Ok(None);
+    }
+    // SAFETY: The mapping is only ever read. A file that is truncated by
+    // another process while it is mapped makes reading the lost pages fail
+    // with SIGBUS. Files that are still being written to, and thus rotated,
+    // are not mapped, which leaves files that are truncated long after their
+    // last write.
+    let Ok(map) = (unsafe { Mmap::map(file) }) else {
+      return Ok(None);
+    };
+    #[cfg(unix)]
+    let _ = map.advise(memmap2::Advice::Sequential);
+    Ok(Some(Mapped { map, pos: 0 }))
+  }
+
+  /// The whole file.
+  pub fn data(&self) -> &[u8] {
+    &self.map
+  }
+
+  /// The part of the file that has not been read yet.
+  pub fn rest(&self) -> &[u8] {
+    &self.map[self.pos..]
+  }
+
+  /// Continues reading at `offset` from the start of the file.
+  pub fn set_position(&mut self, offset: usize) {
+    self.pos = offset.min(self.map.len());
+  }
+}
+
+impl Read for Mapped {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let n = self.rest().read(buf)?;
+    self.pos += n;
+    Ok(n)
+  }
+}
+
+impl BufRead for Mapped {
+  fn fill_buf(&mut self) -> io::Result<&[u8]> {
+    Ok(self.rest())
+  }
+
+  fn consume(&mut self, amt: usize) {
+    self.set_position(self.pos + amt);
+  }
+}
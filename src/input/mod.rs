@@ -0,0 +1,391 @@
+//! Reading lines of Winston output from stdin and log files.
+
+pub mod decompress;
+pub mod envelope;
+pub mod follow;
+pub mod merge;
+pub mod mmap;
+pub mod seek;
+
+use std::{
+  fs::File,
+  io::{self, BufRead, BufReader, Read, Seek},
+  path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use jiff::Timestamp;
+use serde::Deserialize;
+use serde_json::Value;
+
+use self::{
+  decompress::{decompress, is_compressed},
+  envelope::{is_envelope, unwrap_envelopes},
+  mmap::Mapped,
+  seek::{seek_file_to_time, seek_to_time},
+};
+use crate::{prefix, time::parse_timestamp};
+
+/// Capacity of the buffers that input is read through.
+pub const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Receives the lines read from the inputs.
+pub trait LineSink {
+  /// Handles one line, without its line terminator. `source` is the index of
+  /// the input the line was read from.
+  fn line(&mut self, source: usize, line: &[u8]) -> io::Result<()>;
+
+  /// Called whenever no more input is immediately available.
+  fn idle(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+/// Settings for opening inputs.
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+  /// Skip ahead in regular files to the first record logged at or after this
+  /// time, assuming the file is sorted. Records before it may still be read.
+  pub since: Option<Timestamp>,
+}
+
+/// Where lines are read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+  Stdin,
+  File(PathBuf),
+}
+
+impl Input {
+  /// Interprets a command line argument, where `-` means stdin.
+  pub fn from_arg(arg: &Path) -> Input {
+    if arg == Path::new("-") {
+      Input::Stdin
+    } else {
+      Input::File(arg.to_owned())
+    }
+  }
+
+  /// A short name for prefixing lines: the file name without its directory.
+  pub fn name(&self) -> String {
+    match self {
+      Input::Stdin => "stdin".to_owned(),
+      Input::File(path) => path
+        .file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned(),
+    }
+  }
+
+  /// Opens the input for reading, decompressing gzip and zstd archives and
+  /// unwrapping Docker and CRI log envelopes on the fly. Regular files are
+  /// mapped into memory, anything else is read through a buffer.
+  pub fn open(&self, options: &OpenOptions) -> anyhow::Result<Box<dyn BufRead>> {
+    let reader: Box<dyn BufRead> = match self {
+      Input::Stdin => Box::new(io::stdin().lock()),
+      Input::File(path) => {
+        let mut file =
+          File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        let mapped =
+          Mapped::new(&file).with_context(|| format!("failed to read {}", path.display()))?;
+        match mapped {
+          Some(mut mapped) => {
+            if let Some(since) = options.since {
+              skip_to_time(&mut mapped, since);
+            }
+            Box::new(mapped)
+          }
+          None => {
+            if let Some(since) = options.since {
+              skip_file_to_time(&mut file, since)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            }
+            Box::new(BufReader::with_capacity(BUFFER_SIZE, file))
+          }
+        }
+      }
+    };
+    decompress(reader)
+      .and_then(unwrap_envelopes)
+      .with_context(|| format!("failed to read {}", self.name()))
+  }
+
+  /// Whether reading on would have to wait for more input to be written. Only
+  /// stdin stalls, when nothing is ready to be read from the pipe or terminal
+  /// behind it. Input that a decoder of compressed data holds on to is not
+  /// seen.
+  pub fn is_stalled(&self) -> bool {
+    *self == Input::Stdin && !stdin_ready()
+  }
+
+  /// Maps the input into memory if it is a regular file whose lines can be
+  /// read as they are, without decompressing or unwrapping them, and returns
+  /// `None` otherwise. The mapping is positioned like [`Input::open`] would.
+  pub fn map(&self, options: &OpenOptions) -> anyhow::Result<Option<Mapped>> {
+    let Input::File(path) = self else {
+      return Ok(None);
+    };
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mapped =
+      Mapped::new(&file).with_context(|| format!("failed to read {}", path.display()))?;
+    let Some(mut mapped) = mapped else {
+      return Ok(None);
+    };
+    let first = mapped
+      .data()
+      .split(|&b| b == b'\n')
+      .next()
+      .unwrap_or_default();
+    if !is_plain(first) {
+      return Ok(None);
+    }
+    if let Some(since) = options.since {
+      skip_to_time(&mut mapped, since);
+    }
+    Ok(Some(mapped))
+  }
+
+  /// Opens the input if it is a regular file whose lines can be read as they
+  /// are, like [`Input::map`] but without mapping it, and returns `None`
+  /// otherwise. The file is positioned like [`Input::open`] would.
+  pub fn plain_file(&self, options: &OpenOptions) -> anyhow::Result<Option<File>> {
+    let Input::File(path) = self else {
+      return Ok(None);
+    };
+    let mut file =
+      File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let plain =
+      is_plain_file(&mut file).with_context(|| format!("failed to read {}", path.display()))?;
+    if !plain {
+      return Ok(None);
+    }
+    let position = match options.since {
+      Some(since) => seek_file_to_time(&mut file, since),
+      None => file.rewind().map(|()| 0),
+    };
+    position.with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(Some(file))
+  }
+}
+
+/// Whether `file` is a regular file whose first line shows that its lines can
+/// be read as they are.
+fn is_plain_file(file: &mut File) -> io::Result<bool> {
+  if !file.metadata()?.is_file() {
+    return Ok(false);
+  }
+  let mut first = Vec::new();
+  BufReader::new(file.take(BUFFER_SIZE as u64)).read_until(b'\n', &mut first)?;
+  Ok(is_plain(&first))
+}
+
+/// Whether the `first` line of a file shows that its lines can be read as
+/// they are, without decompressing or unwrapping them.
+fn is_plain(first: &[u8]) -> bool {
+  !is_compressed(first) && !is_envelope(trim_newline(first))
+}
+
+/// Positions an uncompressed file at the line where records from `since` on
+/// start, and leaves a compressed one at its start.
+fn skip_to_time(mapped: &mut Mapped, since: Timestamp) {
+  if !is_compressed(mapped.data()) {
+    mapped.set_position(seek_to_time(mapped.data(), since));
+  }
+}
+
+/// Like [`skip_to_time`], for a `file` that is not mapped. Files that cannot
+/// seek, like pipes, are left alone.
+fn skip_file_to_time(file: &mut File, since: Timestamp) -> io::Result<()> {
+  if !file.metadata()?.is_file() {
+    return Ok(());
+  }
+  let mut head = Vec::new();
+  (&mut *file).take(4).read_to_end(&mut head)?;
+  if is_compressed(&head) {
+    file.rewind()
+  } else {
+    seek_file_to_time(file, since).map(drop)
+  }
+}
+
+/// How long stdin may stay silent before it is taken to have stalled, in
+/// milliseconds. Writers that fill a pipe faster than it is read pause for a
+/// moment whenever it is full, which does not count.
+#[cfg(unix)]
+const STALL_MILLIS: libc::c_int = 10;
+
+/// Whether reading stdin returns soon, with input or at its end.
+#[cfg(unix)]
+fn stdin_ready() -> bool {
+  let mut fd = libc::pollfd {
+    fd: libc::STDIN_FILENO,
+    events: libc::POLLIN,
+    revents: 0,
+  };
+  // SAFETY: `fd` is a single, initialized `pollfd`.
+  unsafe { libc::poll(&mut fd, 1, STALL_MILLIS) > 0 }
+}
+
+/// Without `poll`, stdin is taken to stall after every read, which keeps
+/// live output showing up at once.
+#[cfg(not(unix))]
+fn stdin_ready() -> bool {
+  false
+}
+
+/// Reads every input to the end, one after another.
+pub fn read_all<S: LineSink>(
+  inputs: &[Input],
+  options: &OpenOptions,
+  sink: &mut S,
+) -> anyhow::Result<()> {
+  for (source, input) in inputs.iter().enumerate() {
+    read_lines(input.open(options)?, source, sink, || input.is_stalled())?;
+  }
+  Ok(())
+}
+
+/// Feeds every line of `input` to `sink`. Whenever `stalled` says that
+/// reading on would have to wait for more input, `sink` is told to go idle
+/// first, and so it is at the end.
+///
+/// A record that is held back for its continuation lines is thus only let go
+/// when the input pauses, not wherever a buffer happens to end.
+pub fn read_lines<R: BufRead, S: LineSink>(
+  mut input: R,
+  source: usize,
+  sink: &mut S,
+  stalled: impl Fn() -> bool,
+) -> io::Result<()> {
+  let mut lines = LineSplitter::new(source);
+  loop {
+    let buf = input.fill_buf()?;
+    if buf.is_empty() {
+      lines.finish(sink)?;
+      return sink.idle();
+    }
+    let len = buf.len();
+    lines.push(buf, sink)?;
+    input.consume(len);
+    if stalled() {
+      sink.idle()?;
+    }
+  }
+}
+
+/// Splits a stream of chunks into lines.
+#[derive(Debug)]
+pub struct LineSplitter {
+  source: usize,
+  /// The incomplete line at the end of the last chunk.
+  partial: Vec<u8>,
+}
+
+impl LineSplitter {
+  pub fn new(source: usize) -> Self {
+    LineSplitter {
+      source,
+      partial: Vec::new(),
+    }
+  }
+
+  /// Feeds every line completed by `data` to `sink`.
+  pub fn push<S: LineSink>(&mut self, mut data: &[u8], sink: &mut S) -> io::Result<()> {
+    while let Some(end) = memchr::memchr(b'\n', data) {
+      let (line, rest) = data.split_at(end + 1);
+      if self.partial.is_empty() {
+        sink.line(self.source, trim_newline(line))?;
+      } else {
+        self.partial.extend_from_slice(line);
+        sink.line(self.source, trim_newline(&self.partial))?;
+        self.partial.clear();
+      }
+      data = rest;
+    }
+    self.partial.extend_from_slice(data);
+    Ok(())
+  }
+
+  /// Emits a last line that was not terminated by a newline.
+  pub fn finish<S: LineSink>(&mut self, sink: &mut S) -> io::Result<()> {
+    if !self.partial.is_empty() {
+      sink.line(self.source, trim_newline(&self.partial))?;
+      self.partial.clear();
+    }
+    Ok(())
+  }
+
+  /// Forgets the incomplete line, e.g. after the input has been truncated.
+  pub fn clear(&mut self) {
+    self.partial.clear();
+  }
+}
+
+/// Strips a trailing `\n` or `\r\n`.
+pub fn trim_newline(line: &[u8]) -> &[u8] {
+  let line = line.strip_suffix(b"\n").unwrap_or(line);
+  line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Extracts the timestamp of a JSON line without building the whole record.
+/// Behind a known prefix, the prefix's time stands in for a missing one, and
+/// in a Docker log envelope the time of the entry does.
+pub(crate) fn line_timestamp(line: &[u8]) -> Option<Timestamp> {
+  #[derive(Deserialize)]
+  struct Head {
+    timestamp: Option<Value>,
+    log: Option<Value>,
+    time: Option<Value>,
+  }
+
+  if let Ok(head) = serde_json::from_slice::<Head>(line) {
+    if let (None, Some(Value::String(log)), Some(time)) = (&head.timestamp, &head.log, &head.time) {
+      return line_timestamp(trim_newline(log.as_bytes())).or_else(|| parse_timestamp(time));
+    }
+    return parse_timestamp(head.timestamp.as_ref()?);
+  }
+  let (prefix, json) = prefix::split(line)?;
+  let head: Head = serde_json::from_slice(json).ok()?;
+  match head.timestamp {
+    Some(timestamp) => parse_timestamp(&timestamp),
+    None => parse_timestamp(&Value::from(prefix.time?)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Records the lines it is fed, and `idle` where it is told to go idle.
+  #[derive(Default)]
+  struct Recorder(Vec<String>);
+
+  impl LineSink for Recorder {
+    fn line(&mut self, _source: usize, line: &[u8]) -> io::Result<()> {
+      self.0.push(String::from_utf8_lossy(line).into_owned());
+      Ok(())
+    }
+
+    fn idle(&mut self) -> io::Result<()> {
+      self.0.push("idle".to_owned());
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn idle_only_when_stalled() {
+    let data = "first\nsecond\r\nthird";
+    let read = |stalled: bool| {
+      let mut recorder = Recorder::default();
+      let input = BufReader::with_capacity(4, data.as_bytes());
+      read_lines(input, 0, &mut recorder, || stalled).unwrap();
+      recorder.0
+    };
+    assert_eq!(read(false), ["first", "second", "third", "idle"]);
+    assert_eq!(
+      read(true),
+      ["idle", "first", "idle", "idle", "second", "idle", "idle", "third", "idle"]
+    );
+  }
+}
@@ -0,0 +1,231 @@
+//! Binary search for a point in time in sorted log files.
+
+use std::{
+  fs::File,
+  io::{self, BufRead, BufReader, Cursor, Seek, SeekFrom},
+};
+
+use jiff::Timestamp;
+
+use super::line_timestamp;
+
+/// Below this many bytes, the rest of the search is left to reading lines.
+#[cfg(not(test))]
+const LINEAR_THRESHOLD: u64 = 64 * 1024;
+/// Small in tests, so that a few lines take several probes.
+#[cfg(test)]
+const LINEAR_THRESHOLD: u64 = 64;
+
+/// Finds the offset of a line in the sorted file `data` such that no line
+/// before it has a timestamp at or after `since`.
+///
+/// This takes a logarithmic number of probes, each looking at a line or a
+/// few, so the start of a time window can be found in huge files without
+/// scanning them.
+pub fn seek_to_time(data: &[u8], since: Timestamp) -> usize {
+  let len = data.len() as u64;
+  // Reading from memory does not fail.
+  search(&mut Cursor::new(data), len, since).unwrap_or_default() as usize
+}
+
+/// Like [`seek_to_time`], but probes a `file` that is not mapped into memory
+/// by seeking in it, and leaves it positioned at the line found.
+pub fn seek_file_to_time(file: &mut File, since: Timestamp) -> io::Result<u64> {
+  let len = file.metadata()?.len();
+  let offset = search(&mut BufReader::new(&mut *file), len, since)?;
+  file.seek(SeekFrom::Start(offset))
+}
+
+/// The binary search behind [`seek_to_time`] over the first `len` bytes of
+/// `reader`.
+fn search<R: BufRead + Seek>(reader: &mut R, len: u64, since: Timestamp) -> io::Result<u64> {
+  let (mut lo, mut hi) = (0, len);
+  let mut line = Vec::new();
+  while hi - lo > LINEAR_THRESHOLD {
+    let mid = lo + (hi - lo) / 2;
+    match probe(reader, mid, hi, &mut line)? {
+      // Everything before the probed line is older, and so is the line
+      // that `mid` falls into.
+      Some(timestamp) if timestamp < since => lo = mid,
+      _ => hi = mid,
+    }
+  }
+  line_start_after(reader, lo)
+}
+
+/// The timestamp of the first line with one that starts after `offset` but
+/// before `limit`.
+fn probe<R: BufRead + Seek>(
+  reader: &mut R,
+  offset: u64,
+  limit: u64,
+  line: &mut Vec<u8>,
+) -> io::Result<Option<Timestamp>> {
+  let mut pos = line_start_after(reader, offset)?;
+  while pos < limit {
+    line.clear();
+    let n = reader.read_until(b'\n', line)?;
+    if n == 0 {
+      break;
+    }
+    if let Some(timestamp) = line_timestamp(line) {
+      return Ok(Some(timestamp));
+    }
+    pos += n as u64;
+  }
+  Ok(None)
+}
+
+/// The offset of the first line that starts at or after `offset`, where
+/// `reader` is left.
+fn line_start_after<R: BufRead + Seek>(reader: &mut R, offset: u64) -> io::Result<u64> {
+  if offset == 0 {
+    return reader.seek(SeekFrom::Start(0));
+  }
+  // Looking from the byte before `offset` finds a line starting right at it.
+  reader.seek(SeekFrom::Start(offset - 1))?;
+  let skipped = reader.skip_until(b'\n')?;
+  Ok(offset - 1 + skipped as u64)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn at(second: i64) -> Timestamp {
+    Timestamp::from_second(1_791_972_000 + second).unwrap()
+  }
+
+  fn record(second: i64) -> String {
+    format!(
+      r#"{{"level":"info","message":"tick","timestamp":"{}"}}"#,
+      at(second)
+    )
+  }
+
+  /// Lines that start with a digit are records logged at that many seconds,
+  /// anything else is copied as it is.
+  fn log(lines: &[&str]) -> Vec<u8> {
+    let lines: Vec<String> = lines
+      .iter()
+      .map(|line| match line.parse() {
+        Ok(second) => record(second),
+        Err(_) => line.to_string(),
+      })
+      .collect();
+    lines.join("\n").into_bytes()
+  }
+
+  /// Checks that seeking to `since` lands on the start of a line, skips no
+  /// line at or after `since`, and leaves at most about [`LINEAR_THRESHOLD`]
+  /// bytes of older records to be read.
+  fn check(data: &[u8], since: Timestamp) -> usize {
+    let pos = seek_to_time(data, since);
+    assert!(pos <= data.len());
+    assert!(pos == 0 || data[pos - 1] == b'\n', "{pos} is inside a line");
+    let mut offset = 0;
+    let mut older = 0;
+    for line in data.split_inclusive(|&b| b == b'\n') {
+      match line_timestamp(line) {
+        Some(timestamp) if timestamp >= since => break,
+        Some(_) if offset >= pos => older += line.len(),
+        _ => {}
+      }
+      offset += line.len();
+    }
+    assert!(pos <= offset, "skipped the line at {offset} to {pos}");
+    let longest = data.split(|&b| b == b'\n').map(<[u8]>::len).max();
+    assert!(
+      older as u64 <= LINEAR_THRESHOLD + longest.unwrap_or(0) as u64,
+      "left {older} bytes of older records before {offset} to read"
+    );
+    pos
+  }
+
+  #[test]
+  fn before_the_first_line() {
+    let data = log(&["10", "11", "12", "13", "14", "15"]);
+    assert_eq!(check(&data, at(0)), 0);
+    assert_eq!(check(&data, at(10)), 0);
+  }
+
+  #[test]
+  fn after_the_last_line() {
+    let lines: Vec<String> = (0..40).map(|second| second.to_string()).collect();
+    let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+    let mut data = log(&lines);
+    data.push(b'\n');
+    let pos = check(&data, at(100));
+    assert!(pos > data.len() / 2);
+    assert_eq!(check(&[], at(100)), 0);
+  }
+
+  #[test]
+  fn exact_line_boundaries() {
+    let lines: Vec<String> = (0..40).map(|second| (second / 2).to_string()).collect();
+    let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+    let data = log(&lines);
+    for second in 0..22 {
+      check(&data, at(second));
+    }
+    // A line starting right at the probed offset is found.
+    let start = |offset| line_start_after(&mut Cursor::new(&data), offset).unwrap();
+    let line = record(0).len() as u64 + 1;
+    assert_eq!(start(line), line);
+    assert_eq!(start(line - 1), line);
+    assert_eq!(start(line + 1), 2 * line);
+  }
+
+  #[test]
+  fn untimestamped_lines_in_between() {
+    let mut lines = Vec::new();
+    let seconds: Vec<String> = (0..20).map(|second| second.to_string()).collect();
+    for (i, second) in seconds.iter().enumerate() {
+      lines.push(second.as_str());
+      // Stack traces after some records, one longer than the threshold.
+      let frames = if i == 7 { 10 } else { i % 3 };
+      let frame = "    at processTicksAndRejections (node:internal/process:95:5)";
+      lines.extend(std::iter::repeat_n(frame, frames));
+      if i % 4 == 0 {
+        lines.push("npm WARN config production Use `--omit=dev` instead.");
+        lines.push(r#"{"level":"info","message":"no timestamp"}"#);
+      }
+    }
+    let data = log(&lines);
+    for second in 0..22 {
+      check(&data, at(second));
+    }
+  }
+
+  #[test]
+  fn no_trailing_newline() {
+    let lines: Vec<String> = (0..30).map(|second| second.to_string()).collect();
+    let mut lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+    let data = log(&lines);
+    assert_ne!(data.last(), Some(&b'\n'));
+    for second in 0..32 {
+      check(&data, at(second));
+    }
+    lines.push("    at main (app.js:1:1)");
+    let data = log(&lines);
+    for second in 25..32 {
+      check(&data, at(second));
+    }
+  }
+
+  #[test]
+  fn files_are_searched_like_memory() {
+    let lines: Vec<String> = (0..40).map(|second| (second / 2).to_string()).collect();
+    let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+    let data = log(&lines);
+    let path = std::env::temp_dir().join(format!("winstonjson-seek-{}.log", std::process::id()));
+    std::fs::write(&path, &data).unwrap();
+    let mut file = File::open(&path).unwrap();
+    for second in 0..22 {
+      let offset = seek_file_to_time(&mut file, at(second)).unwrap();
+      assert_eq!(offset, seek_to_time(&data, at(second)) as u64);
+      assert_eq!(file.stream_position().unwrap(), offset);
+    }
+    std::fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,105 @@
+use crate::style::{Color, Style};
+
+/// A single logging level, e.g. Winston's `info`.
+#[derive(Debug, Clone)]
+pub struct Level {
+  pub name: String,
+  /// Winston's numeric priority: lower numbers are more severe.
+  pub priority: u32,
+  pub style: Style,
+}
+
+/// An ordered set of levels, the equivalent of a `winston.config.*` entry.
+#[derive(Debug, Clone)]
+pub struct Levels {
+  levels: Vec<Level>,
+}
+
+impl Levels {
+  /// Builds a set from levels listed from most to least severe.
+  pub fn new(levels: Vec<Level>) -> Self {
+    Levels { levels }
+  }
+
+  /// Builds a set from `(name, color)` pairs listed from most to least severe.
+  fn from_colors(levels: &[(&str, Color)]) -> Self {
+    Levels {
+      levels: levels
+        .iter()
+        .zip(0..)
+        .map(|(&(name, color), priority)| Level {
+          name: name.to_owned(),
+          priority,
+          style: Style::new().fg(color),
+        })
+        .collect(),
+    }
+  }
+
+  /// Winston's default levels and the colors `winston.format.colorize()`
+  /// assigns to them.
+  pub fn npm() -> Self {
+    Levels::from_colors(&[
+      ("error", Color::Red),
+      ("warn", Color::Yellow),
+      ("info", Color::Green),
+      ("http", Color::Green),
+      ("verbose", Color::Cyan),
+      ("debug", Color::Blue),
+      ("silly", Color::Magenta),
+    ])
+  }
+
+  /// Winston's `syslog` levels, as in `winston.config.syslog`.
+  pub fn syslog() -> Self {
+    Levels::from_colors(&[
+      ("emerg", Color::Red),
+      ("alert", Color::Yellow),
+      ("crit", Color::Red),
+      ("error", Color::Red),
+      ("warning", Color::Red),
+      ("notice", Color::Yellow),
+      ("info", Color::Green),
+      ("debug", Color::Blue),
+    ])
+  }
+
+  /// Winston's `cli` levels, as in `winston.config.cli`.
+  pub fn cli() -> Self {
+    Levels::from_colors(&[
+      ("error", Color::Red),
+      ("warn", Color::Yellow),
+      ("help", Color::Cyan),
+      ("data", Color::Gray),
+      ("info", Color::Green),
+      ("debug", Color::Blue),
+      ("prompt", Color::Gray),
+      ("verbose", Color::Cyan),
+      ("input", Color::Gray),
+      ("silly", Color::Magenta),
+    ])
+  }
+
+  /// Changes the style of the level called `name`, like `winston.addColors()`.
+  /// Unknown levels are ignored.
+  pub fn set_style(&mut self, name: &str, style: Style) {
+    if let Some(level) = self.levels.iter_mut().find(|level| level.name == name) {
+      level.style = style;
+    }
+  }
+
+  /// Iterates over the levels from most to least severe.
+  pub fn iter(&self) -> impl Iterator<Item = &Level> {
+    self.levels.iter()
+  }
+
+  pub fn get(&self, name: &str) -> Option<&Level> {
+    self.levels.iter().find(|level| level.name == name)
+  }
+
+  /// The style for the level called `name`, or a plain style if the level is
+  /// not part of this set.
+  pub fn style(&self, name: &str) -> Style {
+    self.get(name).map(|level| level.style).unwrap_or_default()
+  }
+}
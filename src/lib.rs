@@ -0,0 +1,22 @@
+//! Parsing and colorizing of [Winston](https://github.com/winstonjs/winston)
+//! JSON log records.
+
+pub mod color;
+pub mod config;
+pub mod filter;
+pub mod input;
+pub mod level;
+pub mod meta;
+pub mod output;
+pub mod parallel;
+pub mod prefix;
+pub mod printer;
+pub mod record;
+pub mod render;
+pub mod reverse;
+pub mod stack;
+pub mod style;
+pub mod template;
+pub mod theme;
+pub mod time;
+pub mod tui;
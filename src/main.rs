@@ -1,3 +1,188 @@
-fn main() {
-  println!("Hello, world!");
+mod cli;
+
+use std::{
+  io::{self, BufWriter, IsTerminal},
+  num::NonZeroUsize,
+  path::PathBuf,
+  process::ExitCode,
+  thread,
+};
+
+use anyhow::{bail, Context};
+use clap::Parser;
+use jiff::tz::TimeZone;
+use winstonjson::{
+  color::set_color_level,
+  config::Config,
+  filter::Filter,
+  input::{follow::follow, merge::merge, Input, OpenOptions, BUFFER_SIZE},
+  level::Levels,
+  output::{Encoder, OutputFormat},
+  parallel,
+  printer::Printer,
+  render::Renderer,
+  reverse::{read_backward, Backward},
+  template::Template,
+  theme::{set_theme, Theme},
+  time::{parse_time_bound, parse_time_zone, TimeDisplay, TimeFormat},
+  tui::{self, App, Source},
+};
+
+use crate::cli::{Cli, Command};
+
+fn main() -> ExitCode {
+  match run() {
+    Ok(()) => ExitCode::SUCCESS,
+    // The reader of our output went away (e.g. `winstonjson | head`).
+    Err(err) if is_broken_pipe(&err) => ExitCode::SUCCESS,
+    Err(err) => {
+      eprintln!("winstonjson: {err:#}");
+      ExitCode::FAILURE
+    }
+  }
+}
+
+fn run() -> anyhow::Result<()> {
+  let cli = Cli::parse();
+  // The TUI draws on the terminal whatever stdout is.
+  let is_terminal = cli.command.is_some() || io::stdout().is_terminal();
+  set_color_level(cli.color.level(is_terminal));
+  let config = Config::load(cli.config.as_deref())?;
+  let mut levels = match cli.levels {
+    Some(levels) => levels.levels(),
+    None => config.levels().unwrap_or_else(Levels::npm),
+  };
+  let theme = cli
+    .theme
+    .as_deref()
+    .or(config.theme.as_deref())
+    .unwrap_or("default");
+  let theme = Theme::named(theme, &config.themes)?;
+  theme.apply_levels(&mut levels);
+  config.apply_colors(&mut levels);
+  set_theme(theme);
+  let since = cli.since.as_deref().map(parse_time_bound).transpose()?;
+  let until = cli.until.as_deref().map(parse_time_bound).transpose()?;
+  let mut filter = Filter::new();
+  if let Some(level) = &cli.level {
+    filter = filter.min_level(&levels, level)?;
+  }
+  for expr in &cli.filter {
+    filter = filter.expr(expr)?;
+  }
+  filter = filter.time_window(since, until);
+  let tail_filter = filter.clone();
+  let time = time_display(&cli)?;
+  // Elapsed times need the records in order.
+  let threads = if time.is_sequential() {
+    NonZeroUsize::MIN
+  } else {
+    cli
+      .threads
+      .or_else(|| thread::available_parallelism().ok())
+      .unwrap_or(NonZeroUsize::MIN)
+  };
+  let options = OpenOptions { since };
+
+  if let Some(Command::Tui(args)) = &cli.command {
+    let inputs = inputs(&args.files);
+    if args.follow && inputs.contains(&Input::Stdin) {
+      bail!("--follow only works with files, not stdin");
+    }
+    let names = inputs.iter().map(Input::name).collect();
+    let app = App::new(levels, filter, time, names)
+      .non_json(cli.non_json)
+      .join_continuations(cli.join)
+      .tail(args.follow);
+    let source = Source {
+      inputs,
+      options,
+      merge: true,
+      follow: if args.follow {
+        args.files.clone()
+      } else {
+        Vec::new()
+      },
+    };
+    return tui::run(app, source);
+  }
+
+  // Stdout is line buffered; the printer flushes whenever the input runs dry
+  // instead.
+  let out = BufWriter::with_capacity(BUFFER_SIZE, io::stdout().lock());
+  let mut printer = if cli.output == OutputFormat::Text {
+    let template = match cli.format.as_ref().or(config.format.as_ref()) {
+      Some(format) => format
+        .parse()
+        .with_context(|| format!("invalid format `{format}`"))?,
+      None => Template::default(),
+    };
+    let meta_mode = cli.meta.or(config.meta).unwrap_or_default();
+    let renderer = Renderer::new(levels, template)
+      .meta_mode(meta_mode)
+      .time_display(time);
+    Printer::new(out, filter, renderer)
+  } else {
+    let encoder = Encoder::new(cli.output)
+      .columns(&cli.columns)?
+      .time_display(time);
+    Printer::new(out, filter, Renderer::new(levels, Template::default())).encoder(encoder)
+  };
+  printer = printer.non_json(cli.non_json).join_continuations(cli.join);
+  let inputs = inputs(&cli.files);
+  if cli.prefix && cli.output == OutputFormat::Text {
+    let names: Vec<String> = inputs.iter().map(Input::name).collect();
+    printer = printer.prefix_sources(&names);
+  }
+  let follows = cli.follow && !cli.files.is_empty();
+  if follows && inputs.contains(&Input::Stdin) {
+    bail!("--follow only works with files, not stdin");
+  }
+  if cli.reverse || cli.tail.is_some() {
+    let backward = Backward {
+      merge: !cli.no_merge,
+      count: cli.tail,
+      reverse: cli.reverse,
+      hidden_lines: printer.shows_lines_of_hidden_records(),
+    };
+    read_backward(&inputs, &options, &tail_filter, backward, &mut printer)?;
+    if follows {
+      follow(&cli.files, &mut printer)?;
+    }
+  } else if follows {
+    follow(&cli.files, &mut printer)?;
+  } else if inputs.len() > 1 && !cli.no_merge {
+    merge(&inputs, &options, &mut printer)?;
+  } else {
+    parallel::read_all(&inputs, &options, &mut printer, threads)?;
+  }
+  printer.flush()?;
+  Ok(())
+}
+
+/// The inputs named on the command line, stdin if there are none.
+fn inputs(files: &[PathBuf]) -> Vec<Input> {
+  match files {
+    [] => vec![Input::Stdin],
+    files => files.iter().map(|file| Input::from_arg(file)).collect(),
+  }
+}
+
+fn time_display(cli: &Cli) -> anyhow::Result<TimeDisplay> {
+  let tz = match &cli.tz {
+    Some(name) => parse_time_zone(name)?,
+    None => TimeZone::system(),
+  };
+  let format = match (&cli.time_format, &cli.tz) {
+    (Some(format), _) => format.clone(),
+    (None, Some(_)) => "iso".parse()?,
+    (None, None) => TimeFormat::Original,
+  };
+  Ok(TimeDisplay::new(format, tz))
+}
+
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+  err
+    .downcast_ref::<io::Error>()
+    .is_some_and(|err| err.kind() == io::ErrorKind::BrokenPipe)
 }
@@ -0,0 +1,584 @@
+//! Syntax-highlighted rendering of a record's metadata fields.
+
+use std::{
+  fmt::{self, Write as _},
+  io::{self, Write},
+};
+
+use serde::{
+  de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor},
+  Deserialize, Deserializer,
+};
+use serde_json::{Number, Value};
+use smallvec::SmallVec;
+
+use crate::{
+  record::{Field, Key},
+  style::{Style, StyledOut},
+  theme::theme,
+};
+
+/// Indentation of expanded metadata below the log line.
+const INDENT: &str = "    ";
+
+/// How metadata is shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum MetaMode {
+  /// `key=value` pairs on the log line, nested objects flattened to
+  /// `outer.inner=value`.
+  #[default]
+  Inline,
+  /// An indented YAML-like tree below the log line.
+  Expanded,
+  /// A compact JSON object on the log line.
+  Json,
+  /// Not at all.
+  Hidden,
+}
+
+/// Renders `fields` as `key=value` pairs. Every token is styled on top of
+/// `base`.
+pub fn inline<'a, 'f: 'a>(
+  fields: impl Iterator<Item = (&'a str, &'a Field<'f>)>,
+  base: Style,
+) -> String {
+  let mut out = String::new();
+  write_inline(&mut out, fields, base);
+  out
+}
+
+/// Like [`inline`], but writes to any [`StyledOut`].
+pub fn write_inline<'a, 'f: 'a, O: StyledOut>(
+  out: &mut O,
+  fields: impl Iterator<Item = (&'a str, &'a Field<'f>)>,
+  base: Style,
+) {
+  let mut first = true;
+  for (key, field) in fields {
+    let path = Path {
+      parent: None,
+      name: key,
+    };
+    // Fields hold valid JSON, so visiting them cannot fail.
+    let _ = field.visit(Pairs {
+      out: &mut *out,
+      first: &mut first,
+      path: &path,
+      base,
+    });
+  }
+}
+
+/// A dotted key, as the chain of names it is made of.
+struct Path<'p> {
+  parent: Option<&'p Path<'p>>,
+  name: &'p str,
+}
+
+impl<'p> Path<'p> {
+  fn parts(&self, parts: &mut SmallVec<[&'p str; 8]>) {
+    if let Some(parent) = self.parent {
+      parent.parts(parts);
+      parts.push(".");
+    }
+    parts.push(self.name);
+  }
+}
+
+/// Writes a value as the `key=value` pairs of its leaves, with nested objects
+/// flattened into dotted keys.
+struct Pairs<'o, 'p, O> {
+  out: &'o mut O,
+  first: &'o mut bool,
+  path: &'p Path<'p>,
+  base: Style,
+}
+
+impl<'o, O: StyledOut> Pairs<'o, '_, O> {
+  /// Writes `key=` and hands back the writer for the value.
+  fn start(self) -> Json<'o, O> {
+    if !*self.first {
+      self.out.push_styled(" ", Style::new());
+    }
+    *self.first = false;
+    let mut key = SmallVec::new();
+    self.path.parts(&mut key);
+    self
+      .out
+      .push_styled_parts(&key, theme().key.patch(self.base));
+    push_styled(self.out, "=", theme().punctuation, self.base);
+    Json {
+      out: self.out,
+      base: self.base,
+    }
+  }
+}
+
+impl<'de, O: StyledOut> DeserializeSeed<'de> for Pairs<'_, '_, O> {
+  type Value = ();
+
+  fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+    deserializer.deserialize_any(self)
+  }
+}
+
+impl<'de, O: StyledOut> Visitor<'de> for Pairs<'_, '_, O> {
+  type Value = ();
+
+  fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("a JSON value")
+  }
+
+  fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+    self.start().visit_unit()
+  }
+
+  fn visit_bool<E: de::Error>(self, v: bool) -> Result<(), E> {
+    self.start().visit_bool(v)
+  }
+
+  fn visit_i64<E: de::Error>(self, v: i64) -> Result<(), E> {
+    self.start().visit_i64(v)
+  }
+
+  fn visit_u64<E: de::Error>(self, v: u64) -> Result<(), E> {
+    self.start().visit_u64(v)
+  }
+
+  fn visit_f64<E: de::Error>(self, v: f64) -> Result<(), E> {
+    self.start().visit_f64(v)
+  }
+
+  fn visit_str<E: de::Error>(self, v: &str) -> Result<(), E> {
+    if !is_bare(v) {
+      return self.start().visit_str(v);
+    }
+    let base = self.base;
+    push_styled(self.start().out, v, theme().string, base);
+    Ok(())
+  }
+
+  fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<(), A::Error> {
+    self.start().visit_seq(seq)
+  }
+
+  fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+    let Pairs {
+      out,
+      first,
+      path,
+      base,
+    } = self;
+    let mut empty = true;
+    while let Some(Key(name)) = map.next_key()? {
+      empty = false;
+      let path = Path {
+        parent: Some(path),
+        name: &name,
+      };
+      map.next_value_seed(Pairs {
+        out: &mut *out,
+        first: &mut *first,
+        path: &path,
+        base,
+      })?;
+    }
+    if empty {
+      let mut json = Pairs {
+        out,
+        first,
+        path,
+        base,
+      }
+      .start();
+      json.push("{", theme().punctuation);
+      json.push("}", theme().punctuation);
+    }
+    Ok(())
+  }
+}
+
+/// Whether a string can be shown without quotes in `key=value` form, on the
+/// log line as well as in logfmt output.
+pub(crate) fn is_bare(s: &str) -> bool {
+  !s.is_empty()
+    && !s
+      .chars()
+      .any(|c| c.is_whitespace() || c.is_control() || matches!(c, '"' | '=' | '\\'))
+}
+
+/// Renders `fields` as a compact JSON object.
+pub fn json<'a, 'f: 'a>(
+  fields: impl Iterator<Item = (&'a str, &'a Field<'f>)>,
+  base: Style,
+) -> String {
+  let mut out = String::new();
+  write_json(&mut out, fields, base);
+  out
+}
+
+/// Like [`json`], but writes to any [`StyledOut`].
+pub fn write_json<'a, 'f: 'a, O: StyledOut>(
+  out: &mut O,
+  fields: impl Iterator<Item = (&'a str, &'a Field<'f>)>,
+  base: Style,
+) {
+  push_styled(out, "{", theme().punctuation, base);
+  for (i, (key, field)) in fields.enumerate() {
+    if i > 0 {
+      push_styled(out, ",", theme().punctuation, base);
+    }
+    push_quoted(out, key, theme().key.patch(base));
+    push_styled(out, ":", theme().punctuation, base);
+    let _ = field.visit(Json {
+      out: &mut *out,
+      base,
+    });
+  }
+  push_styled(out, "}", theme().punctuation, base);
+}
+
+/// Writes `value` as compact JSON.
+fn push_json<O: StyledOut>(out: &mut O, value: &Value, base: Style) {
+  let _ = value.deserialize_any(Json { out, base });
+}
+
+/// Writes a value as compact JSON.
+struct Json<'o, O> {
+  out: &'o mut O,
+  base: Style,
+}
+
+impl<O: StyledOut> Json<'_, O> {
+  fn push(&mut self, text: &str, style: Style) {
+    push_styled(self.out, text, style, self.base);
+  }
+
+  fn push_number(&mut self, number: Number) {
+    let mut text = NumberText::default();
+    // Numbers are far shorter than the buffer.
+    let _ = write!(text, "{number}");
+    self.push(text.as_str(), theme().number);
+  }
+
+  /// A writer for the next item of an array or object, after a `,` unless it
+  /// is the first.
+  fn item(&mut self, first: bool) -> Item<'_, O> {
+    Item {
+      json: Json {
+        out: &mut *self.out,
+        base: self.base,
+      },
+      first,
+    }
+  }
+}
+
+impl<'de, O: StyledOut> DeserializeSeed<'de> for Json<'_, O> {
+  type Value = ();
+
+  fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+    deserializer.deserialize_any(self)
+  }
+}
+
+impl<'de, O: StyledOut> Visitor<'de> for Json<'_, O> {
+  type Value = ();
+
+  fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("a JSON value")
+  }
+
+  fn visit_unit<E: de::Error>(mut self) -> Result<(), E> {
+    self.push("null", theme().null);
+    Ok(())
+  }
+
+  fn visit_bool<E: de::Error>(mut self, v: bool) -> Result<(), E> {
+    self.push(if v { "true" } else { "false" }, theme().boolean);
+    Ok(())
+  }
+
+  fn visit_i64<E: de::Error>(mut self, v: i64) -> Result<(), E> {
+    self.push_number(v.into());
+    Ok(())
+  }
+
+  fn visit_u64<E: de::Error>(mut self, v: u64) -> Result<(), E> {
+    self.push_number(v.into());
+    Ok(())
+  }
+
+  fn visit_f64<E: de::Error>(mut self, v: f64) -> Result<(), E> {
+    match Number::from_f64(v) {
+      Some(number) => self.push_number(number),
+      None => self.push("null", theme().null),
+    }
+    Ok(())
+  }
+
+  fn visit_str<E: de::Error>(self, v: &str) -> Result<(), E> {
+    push_quoted(self.out, v, theme().string.patch(self.base));
+    Ok(())
+  }
+
+  fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+    self.push("[", theme().punctuation);
+    let mut first = true;
+    while seq.next_element_seed(self.item(first))?.is_some() {
+      first = false;
+    }
+    self.push("]", theme().punctuation);
+    Ok(())
+  }
+
+  fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+    self.push("{", theme().punctuation);
+    let mut first = true;
+    while let Some(Key(key)) = map.next_key()? {
+      if !first {
+        self.push(",", theme().punctuation);
+      }
+      first = false;
+      push_quoted(self.out, &key, theme().key.patch(self.base));
+      self.push(":", theme().punctuation);
+      map.next_value_seed(Json {
+        out: &mut *self.out,
+        base: self.base,
+      })?;
+    }
+    self.push("}", theme().punctuation);
+    Ok(())
+  }
+}
+
+/// An item of an array, written with a `,` before it unless it is the first.
+struct Item<'o, O> {
+  json: Json<'o, O>,
+  first: bool,
+}
+
+impl<'de, O: StyledOut> DeserializeSeed<'de> for Item<'_, O> {
+  type Value = ();
+
+  fn deserialize<D: Deserializer<'de>>(mut self, deserializer: D) -> Result<(), D::Error> {
+    if !self.first {
+      self.json.push(",", theme().punctuation);
+    }
+    deserializer.deserialize_any(self.json)
+  }
+}
+
+/// A buffer on the stack to format numbers in.
+#[derive(Default)]
+struct NumberText {
+  bytes: [u8; 32],
+  len: usize,
+}
+
+impl NumberText {
+  fn as_str(&self) -> &str {
+    std::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+  }
+}
+
+impl fmt::Write for NumberText {
+  fn write_str(&mut self, s: &str) -> fmt::Result {
+    let end = self.len + s.len();
+    self
+      .bytes
+      .get_mut(self.len..end)
+      .ok_or(fmt::Error)?
+      .copy_from_slice(s.as_bytes());
+    self.len = end;
+    Ok(())
+  }
+}
+
+/// Writes `value` as JSON indented by two spaces per level, with a `\n`
+/// starting every line after the first.
+pub fn write_pretty<O: StyledOut>(out: &mut O, value: &Value) {
+  push_pretty(out, value, 0);
+}
+
+fn push_pretty<O: StyledOut>(out: &mut O, value: &Value, depth: usize) {
+  let newline = |out: &mut O, depth: usize| {
+    out.push_styled(&format!("\n{:1$}", "", depth * 2), Style::new());
+  };
+  match value {
+    Value::Array(items) if !items.is_empty() => {
+      out.push_styled("[", theme().punctuation);
+      for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+          out.push_styled(",", theme().punctuation);
+        }
+        newline(out, depth + 1);
+        push_pretty(out, item, depth + 1);
+      }
+      newline(out, depth);
+      out.push_styled("]", theme().punctuation);
+    }
+    Value::Object(map) if !map.is_empty() => {
+      out.push_styled("{", theme().punctuation);
+      for (i, (key, value)) in map.iter().enumerate() {
+        if i > 0 {
+          out.push_styled(",", theme().punctuation);
+        }
+        newline(out, depth + 1);
+        push_quoted(out, key, theme().key);
+        out.push_styled(": ", theme().punctuation);
+        push_pretty(out, value, depth + 1);
+      }
+      newline(out, depth);
+      out.push_styled("}", theme().punctuation);
+    }
+    _ => push_json(out, value, Style::new()),
+  }
+}
+
+/// Writes `s` as a JSON string literal.
+fn push_quoted<O: StyledOut>(out: &mut O, s: &str, style: Style) {
+  if s.bytes().any(|b| b < 0x20 || b == b'"' || b == b'\\') {
+    out.push_styled(
+      &serde_json::to_string(s).expect("strings can be serialized"),
+      style,
+    );
+  } else {
+    out.push_styled_parts(&["\"", s, "\""], style);
+  }
+}
+
+fn push_styled<O: StyledOut>(out: &mut O, text: &str, style: Style, base: Style) {
+  out.push_styled(text, style.patch(base));
+}
+
+/// Writes `fields` as an indented, YAML-like tree below a log line.
+pub fn expanded<'a, 'f: 'a, W: Write>(
+  fields: impl Iterator<Item = (&'a str, &'a Field<'f>)>,
+  out: &mut W,
+) -> io::Result<()> {
+  for (key, field) in fields {
+    write_entry(out, INDENT, &key_head(key), field.value())?;
+  }
+  Ok(())
+}
+
+fn key_head(key: &str) -> String {
+  format!(
+    "{}{}",
+    theme().key.paint(key),
+    theme().punctuation.paint(":")
+  )
+}
+
+/// Writes `head value`, or `head` followed by the nested lines of an object,
+/// array or multi-line string. `head` is `key:` or the `-` of an array item.
+fn write_entry<W: Write>(out: &mut W, indent: &str, head: &str, value: &Value) -> io::Result<()> {
+  let nested = format!("{indent}  ");
+  match value {
+    Value::Object(map) if !map.is_empty() => {
+      writeln!(out, "{indent}{head}")?;
+      for (key, value) in map {
+        write_entry(out, &nested, &key_head(key), value)?;
+      }
+    }
+    Value::Array(items) if !items.is_empty() => {
+      writeln!(out, "{indent}{head}")?;
+      let dash = theme().punctuation.paint("-").to_string();
+      for item in items {
+        write_entry(out, &nested, &dash, item)?;
+      }
+    }
+    Value::String(s) if s.contains('\n') => {
+      writeln!(out, "{indent}{head} |")?;
+      for line in s.lines() {
+        writeln!(out, "{nested}{}", theme().string.paint(line))?;
+      }
+    }
+    _ => {
+      let mut scalar = String::new();
+      match value {
+        Value::String(s) => push_styled(&mut scalar, s, theme().string, Style::new()),
+        _ => push_json(&mut scalar, value, Style::new()),
+      }
+      writeln!(out, "{indent}{head} {scalar}")?;
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    level::Levels,
+    record::{strip_ansi, Record},
+    render::Renderer,
+  };
+
+  fn record(line: &str) -> Record<'_> {
+    Record::parse(line.as_bytes()).unwrap()
+  }
+
+  fn render(mode: MetaMode, line: &str) -> String {
+    let mut renderer = Renderer::new(Levels::npm(), Default::default()).meta_mode(mode);
+    let mut out = Vec::new();
+    renderer.render(&record(line), &mut out).unwrap();
+    strip_ansi(&String::from_utf8(out).unwrap())
+  }
+
+  #[test]
+  fn nested_objects_are_flattened() {
+    let record =
+      record(r#"{"req":{"url":"/health","headers":{"host":"api"}},"empty":{},"ids":[1,2]}"#);
+    assert_eq!(
+      strip_ansi(&inline(record.meta(), Style::new())),
+      "req.url=/health req.headers.host=api empty={} ids=[1,2]"
+    );
+  }
+
+  #[test]
+  fn strings_are_quoted_unless_bare() {
+    let record = record(
+      r#"{"user":"ada","query":"a b","eq":"k=v","quote":"say \"hi\"","path":"C:\\tmp","empty":""}"#,
+    );
+    assert_eq!(
+      strip_ansi(&inline(record.meta(), Style::new())),
+      r#"user=ada query="a b" eq="k=v" quote="say \"hi\"" path="C:\\tmp" empty="""#
+    );
+  }
+
+  #[test]
+  fn fields_are_expanded_below_the_line() {
+    let record =
+      record(r#"{"req":{"url":"/health","tags":["a",{"b":true}]},"note":"one\ntwo","n":1.5}"#);
+    let mut out = Vec::new();
+    expanded(record.meta(), &mut out).unwrap();
+    assert_eq!(
+      strip_ansi(&String::from_utf8(out).unwrap()),
+      concat!(
+        "    req:\n",
+        "      url: /health\n",
+        "      tags:\n",
+        "        - a\n",
+        "        -\n",
+        "          b: true\n",
+        "    note: |\n",
+        "      one\n",
+        "      two\n",
+        "    n: 1.5\n",
+      )
+    );
+  }
+
+  #[test]
+  fn json_and_hidden_modes() {
+    let line = r#"{"level":"info","message":"hello","req":{"url":"/health"},"ms":12}"#;
+    assert_eq!(
+      render(MetaMode::Json, line),
+      "info: hello {\"req\":{\"url\":\"/health\"},\"ms\":12}\n"
+    );
+    assert_eq!(render(MetaMode::Hidden, line), "info: hello\n");
+  }
+}
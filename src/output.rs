@@ -0,0 +1,241 @@
+//! Re-emitting records in structured formats, for use as a filter stage in
+//! pipelines.
+
+use std::io::{self, Write};
+
+use anyhow::anyhow;
+use serde_json::{Map, Value};
+
+use crate::{
+  meta::is_bare,
+  record::{FieldPath, Record},
+  render::field_text,
+  time::TimeDisplay,
+};
+
+/// The columns of CSV and TSV output unless chosen otherwise.
+pub const DEFAULT_COLUMNS: [&str; 3] = ["timestamp", "level", "message"];
+
+/// How records are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+  /// Colored, human-readable lines.
+  #[default]
+  Text,
+  /// One compact JSON object per line.
+  Ndjson,
+  /// Indented JSON objects.
+  Json,
+  /// Comma-separated values with a header line.
+  Csv,
+  /// Tab-separated values with a header line.
+  Tsv,
+  /// `key=value` pairs, nested objects flattened to `outer.inner=value`.
+  Logfmt,
+}
+
+/// Writes records as NDJSON, JSON, CSV, TSV or logfmt.
+///
+/// Records are normalized first: the timestamp, level and message come first,
+/// ANSI escapes are stripped from the level, and the timestamp is shown as
+/// chosen with [`Encoder::time_display`].
+#[derive(Debug, Clone)]
+pub struct Encoder {
+  format: OutputFormat,
+  /// The fields to write, with the names they are written under.
+  columns: Vec<(String, FieldPath)>,
+  time: TimeDisplay,
+  header_written: bool,
+}
+
+impl Encoder {
+  pub fn new(format: OutputFormat) -> Self {
+    let encoder = Encoder {
+      format,
+      columns: Vec::new(),
+      time: TimeDisplay::default(),
+      header_written: false,
+    };
+    match format {
+      OutputFormat::Csv | OutputFormat::Tsv => encoder
+        .columns(&DEFAULT_COLUMNS)
+        .expect("default columns are valid"),
+      _ => encoder,
+    }
+  }
+
+  /// Only writes the given (nested) fields, in this order. CSV and TSV
+  /// default to [`DEFAULT_COLUMNS`], the other formats to every field; an
+  /// empty list keeps the default.
+  pub fn columns<S: AsRef<str>>(mut self, columns: &[S]) -> anyhow::Result<Self> {
+    if columns.is_empty() {
+      return Ok(self);
+    }
+    self.columns = columns
+      .iter()
+      .map(|name| {
+        let name = name.as_ref();
+        let path = FieldPath::parse(name).ok_or_else(|| anyhow!("invalid column `{name}`"))?;
+        Ok((name.to_owned(), path))
+      })
+      .collect::<anyhow::Result<_>>()?;
+    Ok(self)
+  }
+
+  /// Sets how timestamps are written. As logged by default.
+  pub fn time_display(mut self, time: TimeDisplay) -> Self {
+    self.time = time;
+    self
+  }
+
+  pub fn write<W: Write>(&mut self, record: &Record, out: &mut W) -> io::Result<()> {
+    let record = self.normalize(record);
+    let fields = self.select(&record);
+    match self.format {
+      OutputFormat::Text => unreachable!("text is written by the renderer"),
+      OutputFormat::Ndjson => {
+        serde_json::to_writer(&mut *out, &fields)?;
+        out.write_all(b"\n")
+      }
+      OutputFormat::Json => {
+        serde_json::to_writer_pretty(&mut *out, &fields)?;
+        out.write_all(b"\n")
+      }
+      OutputFormat::Csv | OutputFormat::Tsv => {
+        self.write_header(out)?;
+        self.write_row(out, &record)
+      }
+      OutputFormat::Logfmt => {
+        let mut line = String::new();
+        for (key, value) in &fields {
+          push_logfmt(&mut line, key, value);
+        }
+        writeln!(out, "{}", line.trim_start())
+      }
+    }
+  }
+
+  /// The record with its reserved fields first and cleaned up.
+  fn normalize(&self, record: &Record) -> Record<'static> {
+    let mut fields = Map::new();
+    if let Some(timestamp) = record.timestamp() {
+      let timestamp = if self.time.is_original() {
+        timestamp.clone()
+      } else {
+        Value::String(self.time.render(timestamp).into_owned())
+      };
+      fields.insert("timestamp".to_owned(), timestamp);
+    }
+    if let Some(level) = record.level() {
+      fields.insert("level".to_owned(), Value::String(level.into_owned()));
+    }
+    if let Some(message) = record.message() {
+      fields.insert("message".to_owned(), message.clone());
+    }
+    for (key, field) in record.meta() {
+      fields.insert(key.to_owned(), field.value().clone());
+    }
+    Record::from_fields(fields)
+  }
+
+  /// The chosen columns of `record` that it has, or all of its fields.
+  fn select(&self, record: &Record) -> Map<String, Value> {
+    if self.columns.is_empty() {
+      return record
+        .iter()
+        .map(|(key, value)| (key.to_owned(), value.clone()))
+        .collect();
+    }
+    self
+      .columns
+      .iter()
+      .filter_map(|(name, path)| Some((name.clone(), record.lookup(path)?.clone())))
+      .collect()
+  }
+
+  /// A copy for rendering records on another thread, which leaves the header
+  /// to this encoder.
+  pub(crate) fn fork(&self) -> Encoder {
+    Encoder {
+      header_written: true,
+      ..self.clone()
+    }
+  }
+
+  /// Writes the header line of CSV and TSV output, unless it has been written
+  /// already.
+  pub(crate) fn write_header<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+    let Some((separator, escape)) = self.delimiters() else {
+      return Ok(());
+    };
+    if self.header_written {
+      return Ok(());
+    }
+    self.header_written = true;
+    let header: Vec<String> = self.columns.iter().map(|(name, _)| escape(name)).collect();
+    writeln!(out, "{}", header.join(separator))
+  }
+
+  /// The separator and escaping of CSV and TSV.
+  fn delimiters(&self) -> Option<(&'static str, Escape)> {
+    match self.format {
+      OutputFormat::Csv => Some((",", csv_escape)),
+      OutputFormat::Tsv => Some(("\t", tsv_escape)),
+      _ => None,
+    }
+  }
+
+  fn write_row<W: Write>(&self, out: &mut W, record: &Record) -> io::Result<()> {
+    let (separator, escape) = self.delimiters().expect("only CSV and TSV have rows");
+    let row: Vec<String> = self
+      .columns
+      .iter()
+      .map(|(_, path)| escape(&field_text(record.lookup(path))))
+      .collect();
+    writeln!(out, "{}", row.join(separator))
+  }
+}
+
+/// Escapes a field of a CSV or TSV row.
+type Escape = fn(&str) -> String;
+
+/// Quotes a CSV field if needed, as in RFC 4180.
+fn csv_escape(s: &str) -> String {
+  if s.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", s.replace('"', "\"\""))
+  } else {
+    s.to_owned()
+  }
+}
+
+/// Escapes the characters that would break up a TSV row.
+fn tsv_escape(s: &str) -> String {
+  s.replace('\\', "\\\\")
+    .replace('\t', "\\t")
+    .replace('\n', "\\n")
+    .replace('\r', "\\r")
+}
+
+fn push_logfmt(line: &mut String, key: &str, value: &Value) {
+  match value {
+    Value::Object(map) if !map.is_empty() => {
+      for (inner, value) in map {
+        push_logfmt(line, &format!("{key}.{inner}"), value);
+      }
+    }
+    _ => {
+      let text = match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+      };
+      line.push(' ');
+      line.push_str(key);
+      line.push('=');
+      if is_bare(&text) {
+        line.push_str(&text);
+      } else {
+        line.push_str(&Value::String(text).to_string());
+      }
+    }
+  }
+}
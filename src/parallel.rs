@@ -0,0 +1,455 @@
+//! Parsing, filtering and rendering files on several threads.
+//!
+//! A file is split into chunks on line boundaries, borrowed straight from its
+//! memory mapping where possible, which a pool of workers render into memory
+//! while the main thread writes the results in their original order. The
+//! lines at the start of a chunk that come before its first record belong to
+//! the record at the end of the chunk before, so they are left to the main
+//! thread, which knows how that chunk ended.
+
+use std::{
+  borrow::Cow,
+  collections::BTreeMap,
+  io::{self, BufRead, Write},
+  iter,
+  num::NonZeroUsize,
+  sync::{
+    mpsc::{self, Receiver, Sender},
+    Mutex,
+  },
+  thread,
+};
+
+use crate::{
+  input::{read_lines, trim_newline, Input, LineSink, OpenOptions},
+  printer::{Printer, Segment},
+  record::Record,
+};
+
+/// Files are split into chunks of about this many bytes.
+#[cfg(not(test))]
+const CHUNK_SIZE: usize = 1024 * 1024;
+/// Small in tests, so that a few lines make many chunks.
+#[cfg(test)]
+const CHUNK_SIZE: usize = 256;
+
+/// Chunks that may be read ahead per worker, while the main thread waits for
+/// an earlier one.
+const CHUNKS_PER_WORKER: usize = 4;
+
+/// Reads every input to the end, one after another, like
+/// [`read_all`](crate::input::read_all), but renders files on `threads`
+/// threads. Stdin is read line by line, so that live output shows up at once.
+pub fn read_all<W: Write>(
+  inputs: &[Input],
+  options: &OpenOptions,
+  printer: &mut Printer<W>,
+  threads: NonZeroUsize,
+) -> anyhow::Result<()> {
+  for (source, input) in inputs.iter().enumerate() {
+    if threads.get() == 1 || *input == Input::Stdin {
+      read_lines(input.open(options)?, source, printer, || input.is_stalled())?;
+    } else if let Some(mapped) = input.map(options)? {
+      let chunks = split_chunks(mapped.rest()).map(|chunk| Ok(Cow::Borrowed(chunk)));
+      render_chunks(chunks, source, printer, threads.get())?;
+    } else {
+      let mut reader = input.open(options)?;
+      let mut carry = Vec::new();
+      let chunks = iter::from_fn(|| {
+        next_chunk(&mut *reader, &mut carry)
+          .map(|chunk| chunk.map(Cow::Owned))
+          .transpose()
+      });
+      render_chunks(chunks, source, printer, threads.get())?;
+    }
+  }
+  Ok(())
+}
+
+/// A chunk of input and its index in it.
+type Job<'a> = (usize, Cow<'a, [u8]>);
+
+/// A chunk of input after a worker is done with it.
+struct Rendered<'a> {
+  chunk: Cow<'a, [u8]>,
+  /// The length of the lines before the first record.
+  head: usize,
+  /// The rendered lines from the first record on, if there is one.
+  segment: Option<Segment>,
+}
+
+/// Renders `chunks` of a single input on `threads` workers and writes them in
+/// order.
+fn render_chunks<'a, W: Write>(
+  chunks: impl Iterator<Item = io::Result<Cow<'a, [u8]>>>,
+  source: usize,
+  printer: &mut Printer<W>,
+  threads: usize,
+) -> io::Result<()> {
+  let (job_tx, job_rx) = mpsc::channel::<Job>();
+  let job_rx = Mutex::new(job_rx);
+  let (done_tx, done_rx) = mpsc::channel::<(usize, io::Result<Rendered>)>();
+  thread::scope(|scope| {
+    for _ in 0..threads {
+      let mut worker = printer.fork();
+      let job_rx = &job_rx;
+      let done_tx = done_tx.clone();
+      scope.spawn(move || loop {
+        let job = job_rx.lock().expect("no worker panics").recv();
+        let Ok((index, chunk)) = job else {
+          return;
+        };
+        let rendered = render_chunk(&mut worker, source, chunk);
+        if done_tx.send((index, rendered)).is_err() {
+          return;
+        }
+      });
+    }
+    drop(done_tx);
+
+    let result = dispatch(chunks, source, printer, threads, &job_tx, &done_rx);
+    drop(job_tx);
+    result
+  })?;
+  printer.idle()
+}
+
+/// Hands `chunks` to the workers and writes what they render in order.
+fn dispatch<'a, W: Write>(
+  chunks: impl Iterator<Item = io::Result<Cow<'a, [u8]>>>,
+  source: usize,
+  printer: &mut Printer<W>,
+  threads: usize,
+  job_tx: &Sender<Job<'a>>,
+  done_rx: &Receiver<(usize, io::Result<Rendered<'a>>)>,
+) -> io::Result<()> {
+  let mut next = 0;
+  let mut ready = BTreeMap::new();
+  let mut write_ready = |index: usize, rendered: Rendered<'a>| -> io::Result<()> {
+    ready.insert(index, rendered);
+    while let Some(rendered) = ready.remove(&next) {
+      write_rendered(printer, source, rendered)?;
+      next += 1;
+    }
+    Ok(())
+  };
+  let mut sent = 0;
+  let mut received = 0;
+  for chunk in chunks {
+    job_tx
+      .send((sent, chunk?))
+      .expect("workers outlive the sender");
+    sent += 1;
+    // Finished chunks wait for the ones before them, so only read so far ahead.
+    while sent - received >= threads * CHUNKS_PER_WORKER {
+      let (index, rendered) = done_rx.recv().expect("workers outlive their jobs");
+      received += 1;
+      write_ready(index, rendered?)?;
+    }
+  }
+  while received < sent {
+    let (index, rendered) = done_rx.recv().expect("workers outlive their jobs");
+    received += 1;
+    write_ready(index, rendered?)?;
+  }
+  Ok(())
+}
+
+/// Splits `data` into chunks of about [`CHUNK_SIZE`] bytes that end at the
+/// end of a line.
+fn split_chunks(mut data: &[u8]) -> impl Iterator<Item = &[u8]> {
+  iter::from_fn(move || {
+    if data.is_empty() {
+      return None;
+    }
+    let end = data
+      .get(CHUNK_SIZE..)
+      .and_then(|rest| memchr::memchr(b'\n', rest))
+      .map_or(data.len(), |end| CHUNK_SIZE + end + 1);
+    let (chunk, rest) = data.split_at(end);
+    data = rest;
+    Some(chunk)
+  })
+}
+
+/// Reads about [`CHUNK_SIZE`] bytes up to the end of a line, starting with
+/// the incomplete line `carry` left over from the chunk before.
+fn next_chunk(reader: &mut dyn BufRead, carry: &mut Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+  let mut chunk = Vec::with_capacity(CHUNK_SIZE + carry.len());
+  chunk.append(carry);
+  loop {
+    let buf = reader.fill_buf()?;
+    if buf.is_empty() {
+      return Ok((!chunk.is_empty()).then_some(chunk));
+    }
+    let start = chunk.len();
+    chunk.extend_from_slice(buf);
+    let len = buf.len();
+    reader.consume(len);
+    if chunk.len() >= CHUNK_SIZE {
+      if let Some(end) = memchr::memrchr(b'\n', &chunk[start..]) {
+        *carry = chunk.split_off(start + end + 1);
+        return Ok(Some(chunk));
+      }
+    }
+  }
+}
+
+/// Renders the lines of `chunk` from its first record on.
+fn render_chunk<'a>(
+  printer: &mut Printer<Vec<u8>>,
+  source: usize,
+  chunk: Cow<'a, [u8]>,
+) -> io::Result<Rendered<'a>> {
+  let mut head = None;
+  for (start, line) in Lines::new(&chunk) {
+    if head.is_none() {
+      if Record::parse(line).is_none() {
+        continue;
+      }
+      head = Some(start);
+    }
+    printer.line(source, line)?;
+  }
+  Ok(Rendered {
+    head: head.unwrap_or(chunk.len()),
+    segment: head.map(|_| printer.take_segment()),
+    chunk,
+  })
+}
+
+/// Feeds the lines before the first record of a chunk to `printer`, followed
+/// by what the worker rendered from there on.
+fn write_rendered<W: Write>(
+  printer: &mut Printer<W>,
+  source: usize,
+  rendered: Rendered,
+) -> io::Result<()> {
+  for (_, line) in Lines::new(&rendered.chunk[..rendered.head]) {
+    printer.line(source, line)?;
+  }
+  match rendered.segment {
+    Some(segment) => printer.append(segment),
+    None => Ok(()),
+  }
+}
+
+/// The lines of a chunk, without line terminators, with their offsets.
+struct Lines<'a> {
+  rest: &'a [u8],
+  offset: usize,
+}
+
+impl<'a> Lines<'a> {
+  fn new(chunk: &'a [u8]) -> Self {
+    Lines {
+      rest: chunk,
+      offset: 0,
+    }
+  }
+}
+
+impl<'a> Iterator for Lines<'a> {
+  type Item = (usize, &'a [u8]);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.rest.is_empty() {
+      return None;
+    }
+    let (line, len) = match memchr::memchr(b'\n', self.rest) {
+      Some(end) => (&self.rest[..end], end + 1),
+      None => (self.rest, self.rest.len()),
+    };
+    let start = self.offset;
+    self.offset += len;
+    self.rest = &self.rest[len..];
+    Some((start, trim_newline(line)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    filter::Filter,
+    level::Levels,
+    output::{Encoder, OutputFormat},
+    printer::NonJson,
+    render::Renderer,
+    template::Template,
+  };
+
+  /// Records of which every third fails with a stack trace, some of them
+  /// longer than a chunk, between an npm banner and a line without its end.
+  fn input() -> Vec<u8> {
+    let mut lines = vec!["> api@1.0.0 start".to_owned(), String::new()];
+    for i in 0..60 {
+      let level = ["info", "warn", "error", "debug"][i % 4];
+      lines.push(format!(
+        r#"{{"level":"{level}","message":"request {i}","durationMs":{},"timestamp":"2026-10-16T10:00:{:02}Z"}}"#,
+        i * 37 % 1000,
+        i
+      ));
+      if i % 3 == 0 {
+        lines.push(format!(
+          "TypeError: cannot read properties of undefined ({i})"
+        ));
+        for frame in 0..(i % 7) * 2 {
+          lines.push(format!(
+            "    at handler{frame} (/app/src/routes.js:{i}:{frame})"
+          ));
+        }
+      }
+    }
+    lines.push("(node:4242) Warning: the end".to_owned());
+    lines.join("\n").into_bytes()
+  }
+
+  fn printer(out: &mut Vec<u8>, join: bool, encoder: Option<Encoder>) -> Printer<&mut Vec<u8>> {
+    let filter = Filter::new()
+      .expr("message ~ /TypeError|request 1/ || durationMs > 800")
+      .unwrap();
+    let renderer = Renderer::new(Levels::npm(), Template::default());
+    let printer = Printer::new(out, filter, renderer)
+      .non_json(NonJson::Dim)
+      .join_continuations(join);
+    match encoder {
+      Some(encoder) => printer.encoder(encoder),
+      None => printer,
+    }
+  }
+
+  /// Renders `data` line by line.
+  fn sequential(data: &[u8], join: bool, encoder: Option<Encoder>) -> Vec<u8> {
+    read_sequentially(data, data.len(), join, encoder)
+  }
+
+  /// Renders `data` line by line like on a single thread, reading it through
+  /// a buffer of `capacity` bytes.
+  fn read_sequentially(
+    data: &[u8],
+    capacity: usize,
+    join: bool,
+    encoder: Option<Encoder>,
+  ) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut printer = printer(&mut out, join, encoder);
+    let reader = io::BufReader::with_capacity(capacity, data);
+    read_lines(reader, 0, &mut printer, || false).unwrap();
+    printer.flush().unwrap();
+    out
+  }
+
+  /// Renders `data` in chunks on `threads` workers, borrowing the chunks from
+  /// `data` or reading them from it.
+  fn parallel(
+    data: &[u8],
+    join: bool,
+    encoder: Option<Encoder>,
+    threads: usize,
+    borrow: bool,
+  ) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut printer = printer(&mut out, join, encoder);
+    if borrow {
+      let chunks = split_chunks(data).map(|chunk| Ok(Cow::Borrowed(chunk)));
+      render_chunks(chunks, 0, &mut printer, threads).unwrap();
+    } else {
+      let mut reader = io::BufReader::with_capacity(100, data);
+      let mut carry = Vec::new();
+      let chunks = iter::from_fn(|| {
+        next_chunk(&mut reader, &mut carry)
+          .map(|chunk| chunk.map(Cow::Owned))
+          .transpose()
+      });
+      render_chunks(chunks, 0, &mut printer, threads).unwrap();
+    }
+    printer.flush().unwrap();
+    out
+  }
+
+  #[test]
+  fn chunks_end_at_line_ends() {
+    let data = input();
+    let chunks: Vec<&[u8]> = split_chunks(&data).collect();
+    assert!(chunks.len() > 10, "{} chunks", chunks.len());
+    assert_eq!(chunks.concat(), data);
+    for chunk in &chunks[..chunks.len() - 1] {
+      assert!(chunk.len() > CHUNK_SIZE);
+      assert_eq!(chunk.last(), Some(&b'\n'));
+    }
+
+    let mut reader = io::BufReader::with_capacity(100, &data[..]);
+    let mut carry = Vec::new();
+    let mut read = Vec::new();
+    while let Some(chunk) = next_chunk(&mut reader, &mut carry).unwrap() {
+      assert!(chunk.ends_with(b"\n") || carry.is_empty());
+      read.extend_from_slice(&chunk);
+    }
+    assert_eq!(read, data);
+  }
+
+  #[test]
+  fn threads_render_like_one() {
+    let data = input();
+    // Some chunks start with the continuation lines of a record in the one
+    // before, or hold nothing but those.
+    let chunks: Vec<&[u8]> = split_chunks(&data).collect();
+    assert!(chunks[1..].iter().any(|chunk| chunk.starts_with(b"    at")));
+    assert!(chunks
+      .iter()
+      .any(|chunk| Lines::new(chunk).all(|(_, line)| Record::parse(line).is_none())));
+
+    for join in [false, true] {
+      let expected = sequential(&data, join, None);
+      assert!(expected.len() > 1000);
+      for threads in [1, 4] {
+        for borrow in [true, false] {
+          let rendered = parallel(&data, join, None, threads, borrow);
+          assert_eq!(
+            String::from_utf8_lossy(&rendered),
+            String::from_utf8_lossy(&expected),
+            "join: {join}, threads: {threads}, borrowed: {borrow}"
+          );
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn threads_encode_like_one() {
+    for format in [OutputFormat::Csv, OutputFormat::Ndjson] {
+      let encoder = || Some(Encoder::new(format));
+      let data = input();
+      let expected = sequential(&data, true, encoder());
+      for threads in [1, 4] {
+        assert_eq!(
+          String::from_utf8_lossy(&parallel(&data, true, encoder(), threads, true)),
+          String::from_utf8_lossy(&expected),
+          "{format:?} on {threads} threads"
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn buffer_boundaries_do_not_matter() {
+    let data = input();
+    // The end of the first record that has a continuation line.
+    let text = String::from_utf8_lossy(&data);
+    let boundary = text.find("\nTypeError").unwrap() + 1;
+    let record = text[..boundary].lines().last().unwrap();
+    assert!(Record::parse(record.as_bytes()).is_some());
+
+    for join in [false, true] {
+      let expected = parallel(&data, join, None, 4, true);
+      for capacity in [boundary, 100, 4096] {
+        assert_eq!(
+          String::from_utf8_lossy(&read_sequentially(&data, capacity, join, None)),
+          String::from_utf8_lossy(&expected),
+          "join: {join}, buffers of {capacity} bytes"
+        );
+      }
+    }
+  }
+}
@@ -0,0 +1,230 @@
+//! Recognizing the prefixes that container runtimes, process managers and
+//! journald put in front of each line of a program's output, e.g.
+//!
+//! ```text
+//! 2026-10-16T10:00:00.123456789Z stdout F {"level":"info",...}   CRI (Kubernetes)
+//! 2026-10-16T10:00:00.123456789Z {"level":"info",...}             docker logs -t
+//! [pod/api-7d9f/api] {"level":"info",...}                          kubectl logs --prefix
+//! api-1  | {"level":"info",...}                                    docker compose logs
+//! 0|api      | {"level":"info",...}                                pm2 logs
+//! Oct 16 10:00:00 web1 api[1234]: {"level":"info",...}             journalctl
+//! ```
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::record::Record;
+
+/// An RFC 3339 timestamp as written by Docker, containerd and `journalctl -o
+/// short-iso`, or the zone-less form PM2's `--time` option writes.
+const TIME: &str = r"\d{4}-\d\d-\d\dT\d\d:\d\d:\d\d(?:\.\d+)?(?:Z|[+-]\d\d:?\d\d)?";
+
+/// The patterns of known prefixes, each matching the whole text before the
+/// JSON object. Capture groups are named after the fields they become, except
+/// `time`.
+static PREFIXES: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+  [
+    format!(r"^(?<time>{TIME}) (?<stream>stdout|stderr) [FP] $"),
+    format!(r"^(?<time>{TIME}) $"),
+    format!(r"^\[pod/(?<pod>[^/\]]+)/(?<container>[^\]]+)\] (?:(?<time>{TIME}) )?$"),
+    format!(r"^(?<pm_id>\d+)\|(?<process>[^|]+?)\s*\| (?:(?<time>{TIME}): )?$"),
+    format!(r"^(?<container>[\w.-]+)\s*\| (?:(?<time>{TIME}) )?$"),
+    format!(
+      r"^(?<time>{TIME}|[A-Z][a-z]{{2}} [ \d]\d \d\d:\d\d:\d\d) (?<host>\S+) (?<process>[^\s\[\]:]+)(?:\[(?<pid>\d+)\])?: $"
+    ),
+  ]
+  .iter()
+  .map(|pattern| Regex::new(pattern).expect("prefix patterns are valid"))
+  .collect()
+});
+
+/// What a prefix says about the line after it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Prefix<'a> {
+  /// When the line was written, if the prefix has a timestamp.
+  pub time: Option<&'a str>,
+  /// Named values such as `stream`, `container`, `pod`, `host`, `process`,
+  /// `pid` or `pm_id`.
+  pub fields: Vec<(&'a str, &'a str)>,
+}
+
+impl Prefix<'_> {
+  /// Adds the prefix's values to the fields of `record`, without replacing
+  /// anything the record already has. The time becomes the `timestamp` of
+  /// records without one.
+  pub fn apply(&self, record: &mut Record) {
+    let time = self.time.map(|time| ("timestamp", time));
+    for (name, value) in self.fields.iter().copied().chain(time) {
+      if record.contains_key(name) {
+        continue;
+      }
+      let value = match value.parse::<u64>() {
+        Ok(number) if name != "timestamp" => Value::from(number),
+        _ => Value::from(value),
+      };
+      record.insert(name, value);
+    }
+  }
+}
+
+/// Splits `line` into a known prefix and the JSON object after it. Returns
+/// `None` unless the line starts with a known prefix followed by `{`.
+pub fn split(line: &[u8]) -> Option<(Prefix<'_>, &[u8])> {
+  let start = line.iter().position(|&b| b == b'{')?;
+  if start == 0 {
+    return None;
+  }
+  let (prefix, json) = line.split_at(start);
+  let prefix = std::str::from_utf8(prefix).ok()?;
+  let (regex, captures) = PREFIXES
+    .iter()
+    .find_map(|regex| Some((regex, regex.captures(prefix)?)))?;
+  let mut parsed = Prefix::default();
+  for name in regex.capture_names().flatten() {
+    let Some(value) = captures.name(name) else {
+      continue;
+    };
+    let value = value.as_str().trim();
+    if name == "time" {
+      parsed.time = Some(value);
+    } else {
+      parsed.fields.push((name, value));
+    }
+  }
+  Some((parsed, json))
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  const JSON: &str = r#"{"level":"info","message":"hi"}"#;
+
+  /// Checks what is read from `prefix` followed by [`JSON`], and that the
+  /// JSON is left intact.
+  fn check(prefix: &str, time: Option<&str>, fields: &[(&str, &str)]) {
+    let line = format!("{prefix}{JSON}");
+    let (parsed, json) = split(line.as_bytes()).expect("a known prefix");
+    assert_eq!(json, JSON.as_bytes());
+    assert_eq!(parsed.time, time);
+    assert_eq!(parsed.fields, fields);
+  }
+
+  /// The record after `line`'s prefix, with the prefix applied.
+  fn apply(line: &str) -> Value {
+    let (parsed, json) = split(line.as_bytes()).unwrap();
+    let mut record = Record::parse(json).unwrap();
+    parsed.apply(&mut record);
+    record.into_value()
+  }
+
+  #[test]
+  fn cri() {
+    check(
+      "2026-10-16T10:00:00.123456789Z stdout F ",
+      Some("2026-10-16T10:00:00.123456789Z"),
+      &[("stream", "stdout")],
+    );
+    check(
+      "2026-10-16T10:00:00.1+02:00 stderr P ",
+      Some("2026-10-16T10:00:00.1+02:00"),
+      &[("stream", "stderr")],
+    );
+  }
+
+  #[test]
+  fn docker_timestamps() {
+    check(
+      "2026-10-16T10:00:00.123456789Z ",
+      Some("2026-10-16T10:00:00.123456789Z"),
+      &[],
+    );
+  }
+
+  #[test]
+  fn kubectl_prefix() {
+    check(
+      "[pod/api-7d9f/api] ",
+      None,
+      &[("pod", "api-7d9f"), ("container", "api")],
+    );
+    check(
+      "[pod/api-7d9f/api] 2026-10-16T10:00:00Z ",
+      Some("2026-10-16T10:00:00Z"),
+      &[("pod", "api-7d9f"), ("container", "api")],
+    );
+  }
+
+  #[test]
+  fn docker_compose() {
+    check("api-1  | ", None, &[("container", "api-1")]);
+  }
+
+  #[test]
+  fn pm2() {
+    check("0|api      | ", None, &[("pm_id", "0"), ("process", "api")]);
+    check(
+      "12|worker | 2026-10-16T10:00:00: ",
+      Some("2026-10-16T10:00:00"),
+      &[("pm_id", "12"), ("process", "worker")],
+    );
+  }
+
+  #[test]
+  fn journalctl() {
+    check(
+      "Oct 16 10:00:00 web1 api[1234]: ",
+      Some("Oct 16 10:00:00"),
+      &[("host", "web1"), ("process", "api"), ("pid", "1234")],
+    );
+    check(
+      "2026-10-16T10:00:00+0200 web1 node: ",
+      Some("2026-10-16T10:00:00+0200"),
+      &[("host", "web1"), ("process", "node")],
+    );
+  }
+
+  #[test]
+  fn not_a_prefix() {
+    assert_eq!(split(JSON.as_bytes()), None);
+    assert_eq!(split(b"npm WARN config"), None);
+    assert_eq!(split(format!("hello world {JSON}").as_bytes()), None);
+  }
+
+  #[test]
+  fn apply_keeps_existing_fields() {
+    assert_eq!(
+      apply(
+        r#"Oct 16 10:00:00 web1 api[1234]: {"level":"info","process":"billing","timestamp":"2026-10-16T10:00:00.5Z"}"#
+      ),
+      json!({
+        "level": "info",
+        "process": "billing",
+        "timestamp": "2026-10-16T10:00:00.5Z",
+        "host": "web1",
+        "pid": 1234,
+      })
+    );
+    assert_eq!(
+      apply(r#"[pod/api-7d9f/api] {"pod":"other","message":"hi"}"#),
+      json!({"pod": "other", "message": "hi", "container": "api"})
+    );
+    assert_eq!(
+      apply(&format!("0|api | {JSON}")),
+      json!({"level": "info", "message": "hi", "pm_id": 0, "process": "api"})
+    );
+    assert_eq!(
+      apply(&format!("2026-10-16T10:00:00Z stdout F {JSON}")),
+      json!({
+        "level": "info",
+        "message": "hi",
+        "stream": "stdout",
+        "timestamp": "2026-10-16T10:00:00Z",
+      })
+    );
+  }
+}
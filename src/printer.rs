@@ -0,0 +1,328 @@
+use std::{
+  io::{self, Write},
+  mem,
+};
+
+use crate::{
+  filter::Filter, input::LineSink, output::Encoder, record::Record, render::Renderer, theme::theme,
+};
+
+/// What to do with lines that are not JSON records, such as `console.log`
+/// output, npm banners or uncaught exception dumps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum NonJson {
+  /// Print them unchanged.
+  #[default]
+  Pass,
+  /// Print them dimmed, so the records stand out.
+  Dim,
+  /// Leave them out.
+  Drop,
+  /// Turn them into records with the level `unknown`, which are filtered and
+  /// rendered like any other.
+  Wrap,
+}
+
+/// Filters and renders lines of Winston output to a writer.
+///
+/// Lines that are not JSON objects are handled according to a [`NonJson`]
+/// policy, unless they follow a record outside the time window. With an
+/// [`Encoder`], records are written in a structured format instead, and only
+/// wrapped lines that are not JSON are kept.
+pub struct Printer<W> {
+  out: W,
+  filter: Filter,
+  renderer: Renderer,
+  encoder: Option<Encoder>,
+  /// Rendered source name prefixes, indexed by source.
+  prefixes: Vec<String>,
+  non_json: NonJson,
+  join: bool,
+  /// With `join`, the last record and its source, held back while
+  /// continuation lines may still follow.
+  pending: Option<(usize, Record<'static>)>,
+  /// The source of the last record and whether it was shown.
+  last: Option<(usize, bool)>,
+  /// Whether the last record was inside the time window. Non-JSON lines
+  /// between records share the fate of the record before them.
+  in_window: bool,
+}
+
+impl<W: Write> Printer<W> {
+  pub fn new(out: W, filter: Filter, renderer: Renderer) -> Self {
+    Printer {
+      out,
+      filter,
+      renderer,
+      encoder: None,
+      prefixes: Vec::new(),
+      non_json: NonJson::default(),
+      join: false,
+      pending: None,
+      last: None,
+      in_window: false,
+    }
+  }
+
+  /// Writes records with `encoder` rather than rendering them as text.
+  pub fn encoder(mut self, encoder: Encoder) -> Self {
+    self.encoder = Some(encoder);
+    self
+  }
+
+  /// Sets what to do with lines that are not JSON.
+  pub fn non_json(mut self, policy: NonJson) -> Self {
+    self.non_json = policy;
+    self
+  }
+
+  /// Joins the lines that are not JSON onto the message of the record before
+  /// them from the same source, so that they are filtered along with it.
+  pub fn join_continuations(mut self, join: bool) -> Self {
+    self.join = join;
+    self
+  }
+
+  /// Prefixes every line with the name of its source, each in its own color.
+  pub fn prefix_sources(mut self, names: &[String]) -> Self {
+    let width = names
+      .iter()
+      .map(|name| name.chars().count())
+      .max()
+      .unwrap_or(0);
+    self.prefixes = names
+      .iter()
+      .enumerate()
+      .map(|(source, name)| {
+        format!(
+          "{} ",
+          theme().source(source).paint(format!("{name:width$} |"))
+        )
+      })
+      .collect();
+    self
+  }
+
+  /// Whether the lines that are not JSON below a record hidden by the filter
+  /// are shown.
+  pub fn shows_lines_of_hidden_records(&self) -> bool {
+    !self.join
+      && match self.non_json {
+        NonJson::Pass | NonJson::Dim => self.encoder.is_none(),
+        NonJson::Drop => false,
+        NonJson::Wrap => true,
+      }
+  }
+
+  /// Writes the record held back for continuation lines, and the header of
+  /// CSV and TSV output if no record has been written yet, so that it is
+  /// there even if no record matches.
+  pub fn flush(&mut self) -> io::Result<()> {
+    if let Some((source, record)) = self.pending.take() {
+      self.print(source, &record)?;
+    }
+    if let Some(encoder) = &mut self.encoder {
+      encoder.write_header(&mut self.out)?;
+    }
+    self.out.flush()
+  }
+
+  /// Filters and renders `record`, and remembers whether it was shown.
+  fn print(&mut self, source: usize, record: &Record) -> io::Result<()> {
+    let shown = self.filter.matches(record);
+    self.last = Some((source, shown));
+    if shown {
+      self.write_record(source, record)?;
+    }
+    Ok(())
+  }
+
+  /// A printer with the same settings that renders into memory, for
+  /// rendering chunks of input on another thread. The header of CSV and TSV
+  /// output is left to this printer.
+  pub(crate) fn fork(&self) -> Printer<Vec<u8>> {
+    Printer {
+      out: Vec::new(),
+      filter: self.filter.clone(),
+      renderer: self.renderer.clone(),
+      encoder: self.encoder.as_ref().map(Encoder::fork),
+      prefixes: self.prefixes.clone(),
+      non_json: self.non_json,
+      join: self.join,
+      pending: None,
+      last: None,
+      in_window: false,
+    }
+  }
+
+  /// Writes a segment rendered by a fork, and goes on where it left off, as
+  /// if its lines had been fed to this printer.
+  pub(crate) fn append(&mut self, segment: Segment) -> io::Result<()> {
+    if let Some((source, record)) = self.pending.take() {
+      self.print(source, &record)?;
+    }
+    if !segment.out.is_empty() {
+      if let Some(encoder) = &mut self.encoder {
+        encoder.write_header(&mut self.out)?;
+      }
+      self.out.write_all(&segment.out)?;
+    }
+    self.pending = segment.pending;
+    self.last = segment.last;
+    self.in_window = segment.in_window;
+    Ok(())
+  }
+
+  fn write_record(&mut self, source: usize, record: &Record) -> io::Result<()> {
+    match &mut self.encoder {
+      Some(encoder) => encoder.write(record, &mut self.out),
+      None => {
+        self.write_prefix(source)?;
+        self.renderer.render(record, &mut self.out)
+      }
+    }
+  }
+
+  fn write_prefix(&mut self, source: usize) -> io::Result<()> {
+    if let Some(prefix) = self.prefixes.get(source) {
+      self.out.write_all(prefix.as_bytes())?;
+    }
+    Ok(())
+  }
+
+  fn non_json_line(&mut self, source: usize, line: &[u8]) -> io::Result<()> {
+    if self.join {
+      match &mut self.pending {
+        Some((pending, record)) if *pending == source => {
+          record.append_line(&String::from_utf8_lossy(line));
+          return Ok(());
+        }
+        // The record has already been written because the input paused.
+        _ => match self.last {
+          Some((last, shown)) if last == source => {
+            if shown && self.encoder.is_none() {
+              self.write_prefix(source)?;
+              self
+                .renderer
+                .render_continuation(&String::from_utf8_lossy(line), &mut self.out)?;
+            }
+            return Ok(());
+          }
+          _ => {}
+        },
+      }
+    }
+    if self.filter.has_time_window() && !self.in_window {
+      return Ok(());
+    }
+    match self.non_json {
+      // Raw lines would break up structured output.
+      NonJson::Pass | NonJson::Dim if self.encoder.is_some() => Ok(()),
+      NonJson::Pass => {
+        self.write_prefix(source)?;
+        self.out.write_all(line)?;
+        self.out.write_all(b"\n")
+      }
+      NonJson::Dim => {
+        self.write_prefix(source)?;
+        writeln!(
+          self.out,
+          "{}",
+          theme().dimmed.paint(String::from_utf8_lossy(line))
+        )
+      }
+      NonJson::Drop => Ok(()),
+      NonJson::Wrap => {
+        let record = Record::raw(&String::from_utf8_lossy(line));
+        if self.filter.matches(&record) {
+          self.write_record(source, &record)?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+impl Printer<Vec<u8>> {
+  /// Takes what has been rendered so far, along with the state to go on from.
+  pub(crate) fn take_segment(&mut self) -> Segment {
+    Segment {
+      out: mem::take(&mut self.out),
+      pending: self.pending.take(),
+      last: self.last.take(),
+      in_window: mem::take(&mut self.in_window),
+    }
+  }
+}
+
+/// The output of a [`Printer::fork`] for a chunk of input, with the state at
+/// its end.
+pub(crate) struct Segment {
+  out: Vec<u8>,
+  pending: Option<(usize, Record<'static>)>,
+  last: Option<(usize, bool)>,
+  in_window: bool,
+}
+
+impl<W: Write> LineSink for Printer<W> {
+  fn line(&mut self, source: usize, line: &[u8]) -> io::Result<()> {
+    let Some(record) = Record::parse(line) else {
+      return self.non_json_line(source, line);
+    };
+    if let Some((source, record)) = self.pending.take() {
+      self.print(source, &record)?;
+    }
+    self.in_window = self.filter.in_time_window(&record);
+    if self.join {
+      self.pending = Some((source, record.into_owned()));
+      Ok(())
+    } else {
+      self.print(source, &record)
+    }
+  }
+
+  /// Keeps interactive pipelines like `node app.js | winstonjson` live.
+  fn idle(&mut self) -> io::Result<()> {
+    self.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{level::Levels, output::OutputFormat, template::Template};
+
+  fn encode(format: OutputFormat, filter: &str, lines: &[&str]) -> String {
+    let mut out = Vec::new();
+    let filter = Filter::new().expr(filter).unwrap();
+    let renderer = Renderer::new(Levels::npm(), Template::default());
+    let mut printer = Printer::new(&mut out, filter, renderer).encoder(Encoder::new(format));
+    for line in lines {
+      printer.line(0, line.as_bytes()).unwrap();
+      printer.idle().unwrap();
+    }
+    printer.flush().unwrap();
+    String::from_utf8(out).unwrap()
+  }
+
+  #[test]
+  fn csv_header_without_matches() {
+    let lines = [
+      r#"{"level":"info","message":"a","timestamp":"2026-10-16T10:00:00Z"}"#,
+      r#"{"level":"error","message":"b, c","timestamp":"2026-10-16T10:00:01Z"}"#,
+    ];
+    assert_eq!(
+      encode(OutputFormat::Csv, "level == 'warn'", &lines),
+      "timestamp,level,message\n"
+    );
+    assert_eq!(
+      encode(OutputFormat::Tsv, "level == 'warn'", &[]),
+      "timestamp\tlevel\tmessage\n"
+    );
+    assert_eq!(
+      encode(OutputFormat::Csv, "level == 'error'", &lines),
+      "timestamp,level,message\n2026-10-16T10:00:01Z,error,\"b, c\"\n"
+    );
+    assert_eq!(encode(OutputFormat::Ndjson, "level == 'warn'", &lines), "");
+  }
+}
@@ -0,0 +1,474 @@
+use std::{borrow::Cow, cell::OnceCell, fmt};
+
+use serde::{
+  de::{self, MapAccess, Visitor},
+  Deserialize, Deserializer,
+};
+use serde_json::{value::RawValue, Map, Value};
+use smallvec::SmallVec;
+
+use crate::prefix;
+
+/// Keys that Winston itself puts on every record and that are rendered in
+/// dedicated positions rather than as metadata.
+pub const RESERVED_KEYS: [&str; 3] = ["timestamp", "level", "message"];
+
+/// The level of records made up for lines that are not JSON.
+pub const UNKNOWN_LEVEL: &str = "unknown";
+
+/// A single parsed Winston log record.
+///
+/// Parsing only finds the fields of the line and borrows them from it; each
+/// value is decoded the first time it is looked at. Records that are filtered
+/// out by their level thus cost little more than a scan of the line.
+#[derive(Debug, Clone)]
+pub struct Record<'a> {
+  fields: Fields<'a>,
+}
+
+/// The fields of a record. Typical records fit without a heap allocation.
+type Fields<'a> = SmallVec<[(Cow<'a, str>, Field<'a>); 8]>;
+
+/// A field value, kept as JSON text until it is needed.
+///
+/// Strings without escapes and values that are only written out, like the
+/// metadata on a rendered line, are never decoded at all.
+#[derive(Debug, Clone)]
+pub struct Field<'a> {
+  raw: Option<&'a RawValue>,
+  /// Boxed to keep the fields of a record small.
+  value: OnceCell<Box<Value>>,
+}
+
+impl<'a> Field<'a> {
+  fn raw(raw: &'a RawValue) -> Self {
+    Field {
+      raw: Some(raw),
+      value: OnceCell::new(),
+    }
+  }
+
+  fn decoded(value: Value) -> Self {
+    Field {
+      raw: None,
+      value: OnceCell::from(Box::new(value)),
+    }
+  }
+
+  /// The JSON text of the value, unless it has been decoded.
+  fn text(&self) -> Option<&'a str> {
+    match self.value.get() {
+      Some(_) => None,
+      None => self.raw.map(RawValue::get),
+    }
+  }
+
+  pub fn value(&self) -> &Value {
+    self.value.get_or_init(|| {
+      Box::new(
+        self
+          .raw
+          .and_then(|raw| serde_json::from_str(raw.get()).ok())
+          .unwrap_or_default(),
+      )
+    })
+  }
+
+  /// The value if it is a string, borrowed from the line unless it contains
+  /// escapes.
+  pub fn as_str(&self) -> Option<&str> {
+    if let Some(text) = self.text() {
+      let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+      if !inner.contains('\\') {
+        return Some(inner);
+      }
+    }
+    self.value().as_str()
+  }
+
+  pub fn is_string(&self) -> bool {
+    match self.text() {
+      Some(text) => text.starts_with('"'),
+      None => self.value().is_string(),
+    }
+  }
+
+  /// Hands the value to `visitor`, straight from the JSON text unless it has
+  /// been decoded.
+  pub fn visit<'s, V: Visitor<'s>>(&'s self, visitor: V) -> serde_json::Result<V::Value> {
+    match self.text() {
+      Some(text) => serde_json::Deserializer::from_str(text).deserialize_any(visitor),
+      None => self.value().deserialize_any(visitor),
+    }
+  }
+
+  /// The decoded value, to change in place.
+  fn value_mut(&mut self) -> &mut Value {
+    self.value();
+    self.raw = None;
+    self
+      .value
+      .get_mut()
+      .expect("the value has just been decoded")
+  }
+
+  fn into_value(self) -> Value {
+    self.value();
+    self
+      .value
+      .into_inner()
+      .map(|value| *value)
+      .unwrap_or_default()
+  }
+}
+
+impl<'a> Record<'a> {
+  /// Parses a line of Winston JSON output.
+  ///
+  /// A JSON object behind a known prefix, such as the timestamp and stream
+  /// of Kubernetes container logs, is found as well, and the values of the
+  /// prefix are added to its fields (see [`prefix`](crate::prefix)).
+  ///
+  /// Returns `None` if the line is not a JSON object, in which case the caller
+  /// is expected to pass the line through untouched.
+  pub fn parse(line: &'a [u8]) -> Option<Record<'a>> {
+    if let Some(record) = Record::parse_object(line) {
+      return Some(record);
+    }
+    let (prefix, json) = prefix::split(line)?;
+    let mut record = Record::parse_object(json)?;
+    prefix.apply(&mut record);
+    Some(record)
+  }
+
+  fn parse_object(json: &'a [u8]) -> Option<Record<'a>> {
+    // Most lines that are not records are rejected without starting a parser.
+    if json.iter().find(|b| !b.is_ascii_whitespace()) != Some(&b'{') {
+      return None;
+    }
+    // Checking the encoding up front is faster than letting the parser do it
+    // for every key and value it borrows.
+    let json = std::str::from_utf8(json).ok()?;
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let fields = deserializer.deserialize_map(FieldsVisitor).ok()?;
+    deserializer.end().ok()?;
+    Some(Record { fields })
+  }
+
+  /// A record standing in for a line that is not JSON, with the level
+  /// `unknown` and the line as its message.
+  pub fn raw(line: &str) -> Record<'a> {
+    let mut record = Record {
+      fields: Fields::new(),
+    };
+    record.insert("level", Value::from(UNKNOWN_LEVEL));
+    record.insert("message", Value::from(line));
+    record
+  }
+
+  /// A record with the given fields.
+  pub fn from_fields(fields: Map<String, Value>) -> Record<'static> {
+    Record {
+      fields: fields
+        .into_iter()
+        .map(|(key, value)| (Cow::Owned(key), Field::decoded(value)))
+        .collect(),
+    }
+  }
+
+  /// Copies whatever the record borrows from its line, so that it can outlive
+  /// it.
+  pub fn into_owned(self) -> Record<'static> {
+    Record {
+      fields: self
+        .fields
+        .into_iter()
+        .map(|(key, field)| {
+          (
+            Cow::Owned(key.into_owned()),
+            Field::decoded(field.into_value()),
+          )
+        })
+        .collect(),
+    }
+  }
+
+  /// Sets the field `key`, replacing the value of an existing one in place.
+  pub fn insert(&mut self, key: &str, value: Value) {
+    match self.fields.iter_mut().find(|(name, _)| name == key) {
+      Some((_, field)) => *field = Field::decoded(value),
+      None => self
+        .fields
+        .push((Cow::Owned(key.to_owned()), Field::decoded(value))),
+    }
+  }
+
+  pub fn contains_key(&self, key: &str) -> bool {
+    self.field(key).is_some()
+  }
+
+  /// Joins a continuation line, e.g. of an exception dump, onto the message.
+  pub fn append_line(&mut self, line: &str) {
+    let Some((_, field)) = self.fields.iter_mut().find(|(name, _)| name == "message") else {
+      self.insert("message", Value::from(line));
+      return;
+    };
+    let message = field.value_mut();
+    if !message.is_string() {
+      *message = Value::String(message.to_string());
+    }
+    if let Value::String(message) = message {
+      message.push('\n');
+      message.push_str(line);
+    }
+  }
+
+  /// The record as a JSON object.
+  pub fn into_value(self) -> Value {
+    Value::Object(
+      self
+        .fields
+        .into_iter()
+        .map(|(key, field)| (key.into_owned(), field.into_value()))
+        .collect(),
+    )
+  }
+
+  /// Iterates over all fields in the order of the line.
+  pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+    self
+      .fields
+      .iter()
+      .map(|(key, field)| (key.as_ref(), field.value()))
+  }
+
+  pub fn field(&self, key: &str) -> Option<&Field<'a>> {
+    self
+      .fields
+      .iter()
+      .find(|(name, _)| name == key)
+      .map(|(_, field)| field)
+  }
+
+  pub fn get(&self, key: &str) -> Option<&Value> {
+    self.field(key).map(Field::value)
+  }
+
+  /// The field `key` if it is a string (see [`Field::as_str`]).
+  pub fn get_str(&self, key: &str) -> Option<&str> {
+    self.field(key)?.as_str()
+  }
+
+  /// The level name, with any ANSI escapes left behind by
+  /// `winston.format.colorize()` removed.
+  pub fn level(&self) -> Option<Cow<'_, str>> {
+    let level = self.get_str("level")?;
+    Some(if level.contains('\x1b') {
+      Cow::Owned(strip_ansi(level))
+    } else {
+      Cow::Borrowed(level)
+    })
+  }
+
+  pub fn message(&self) -> Option<&Value> {
+    self.get("message")
+  }
+
+  pub fn timestamp(&self) -> Option<&Value> {
+    self.get("timestamp")
+  }
+
+  /// Iterates over the metadata, i.e. every field except the reserved ones.
+  pub fn meta(&self) -> impl Iterator<Item = (&str, &Field<'a>)> {
+    self
+      .fields
+      .iter()
+      .filter(|(key, _)| !RESERVED_KEYS.contains(&key.as_ref()))
+      .map(|(key, field)| (key.as_ref(), field))
+  }
+
+  /// Resolves `path` against the record's fields.
+  pub fn lookup(&self, path: &FieldPath) -> Option<&Value> {
+    let (first, rest) = path.segments.split_first()?;
+    let mut value = match first {
+      Segment::Key(key) => self.get(key)?,
+      Segment::Index(_) => return None,
+    };
+    for segment in rest {
+      value = match (segment, value) {
+        (Segment::Key(key), Value::Object(map)) => map.get(key)?,
+        (Segment::Key(key), Value::Array(items)) => items.get(key.parse::<usize>().ok()?)?,
+        (Segment::Index(index), Value::Array(items)) => items.get(*index)?,
+        _ => return None,
+      };
+    }
+    Some(value)
+  }
+}
+
+/// Collects the fields of a JSON object without decoding their values. A key
+/// that occurs twice keeps its first position and its last value, as in a
+/// [`Map`].
+struct FieldsVisitor;
+
+impl<'de> Visitor<'de> for FieldsVisitor {
+  type Value = Fields<'de>;
+
+  fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("a JSON object")
+  }
+
+  fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+    let mut fields = Fields::new();
+    while let Some(Key(key)) = map.next_key()? {
+      let field = Field::raw(map.next_value()?);
+      match fields.iter_mut().find(|(name, _)| *name == key) {
+        Some((_, existing)) => *existing = field,
+        None => fields.push((key, field)),
+      }
+    }
+    Ok(fields)
+  }
+}
+
+/// An object key, borrowed from the line unless it contains escapes.
+pub(crate) struct Key<'a>(pub(crate) Cow<'a, str>);
+
+impl<'de> Deserialize<'de> for Key<'de> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    struct KeyVisitor;
+
+    impl<'de> Visitor<'de> for KeyVisitor {
+      type Value = Key<'de>;
+
+      fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a string")
+      }
+
+      fn visit_borrowed_str<E: de::Error>(self, key: &'de str) -> Result<Self::Value, E> {
+        Ok(Key(Cow::Borrowed(key)))
+      }
+
+      fn visit_str<E: de::Error>(self, key: &str) -> Result<Self::Value, E> {
+        Ok(Key(Cow::Owned(key.to_owned())))
+      }
+    }
+
+    deserializer.deserialize_str(KeyVisitor)
+  }
+}
+
+/// Removes ANSI escape sequences (`ESC [ ... letter`) from `s`.
+pub fn strip_ansi(s: &str) -> String {
+  let mut out = String::with_capacity(s.len());
+  let mut chars = s.chars();
+  while let Some(c) = chars.next() {
+    if c == '\x1b' {
+      if chars.next() == Some('[') {
+        for c in chars.by_ref() {
+          if c.is_ascii_alphabetic() {
+            break;
+          }
+        }
+      }
+    } else {
+      out.push(c);
+    }
+  }
+  out
+}
+
+/// A reference to a possibly nested field, e.g. `meta.req.url` or `tags[0]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldPath {
+  segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+  Key(String),
+  Index(usize),
+}
+
+impl FieldPath {
+  /// The name of the top-level field the path starts at.
+  pub fn root(&self) -> &str {
+    match self.segments.first() {
+      Some(Segment::Key(key)) => key,
+      _ => "",
+    }
+  }
+
+  /// Parses a dotted path. Array elements can be addressed as `items[0]` or
+  /// `items.0`.
+  pub fn parse(path: &str) -> Option<FieldPath> {
+    let mut segments = Vec::new();
+    for part in path.split('.') {
+      let (key, mut indices) = match part.find('[') {
+        Some(start) => part.split_at(start),
+        None => (part, ""),
+      };
+      if key.is_empty() {
+        if segments.is_empty() || indices.is_empty() {
+          return None;
+        }
+      } else {
+        segments.push(Segment::Key(key.to_owned()));
+      }
+      while !indices.is_empty() {
+        let (index, rest) = indices.strip_prefix('[')?.split_once(']')?;
+        segments.push(Segment::Index(index.parse().ok()?));
+        indices = rest;
+      }
+    }
+    Some(FieldPath { segments })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(line: &str) -> Record<'_> {
+    Record::parse(line.as_bytes()).unwrap()
+  }
+
+  #[test]
+  fn strings_are_borrowed_unless_escaped() {
+    let line = r#"{"level":"info","message":"a \"quoted\" word","n":1}"#;
+    let record = parse(line);
+    let level = record.get_str("level").unwrap();
+    assert_eq!(level, "info");
+    assert!(line.as_bytes().as_ptr_range().contains(&level.as_ptr()));
+    assert_eq!(record.get_str("message"), Some(r#"a "quoted" word"#));
+    assert_eq!(record.get_str("n"), None);
+    assert!(!record.field("n").unwrap().is_string());
+    assert_eq!(record.get("n"), Some(&Value::from(1)));
+  }
+
+  #[test]
+  fn duplicate_keys_keep_their_first_position() {
+    let record = parse(r#"{"a":1,"b":2,"a":3}"#);
+    let fields: Vec<_> = record
+      .iter()
+      .map(|(key, value)| (key, value.clone()))
+      .collect();
+    assert_eq!(fields, [("a", Value::from(3)), ("b", Value::from(2))]);
+  }
+
+  #[test]
+  fn lines_are_appended_to_the_message() {
+    let mut record = parse(r#"{"message":"Error: boom"}"#);
+    record.append_line("    at main (app.js:1:1)");
+    assert_eq!(
+      record.get_str("message"),
+      Some("Error: boom\n    at main (app.js:1:1)")
+    );
+    let mut record = parse(r#"{"message":{"code":1}}"#);
+    record.append_line("more");
+    assert_eq!(record.get_str("message"), Some("{\"code\":1}\nmore"));
+    let mut record = parse(r#"{"level":"info"}"#);
+    record.append_line("more");
+    assert_eq!(record.get_str("message"), Some("more"));
+  }
+}
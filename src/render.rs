@@ -0,0 +1,360 @@
+use std::{
+  borrow::Cow,
+  io::{self, Write},
+};
+
+use serde_json::Value;
+
+use crate::{
+  level::Levels,
+  meta::{self, MetaMode},
+  record::{Field, Record},
+  stack::render_stack,
+  style::{Style, StyledOut},
+  template::{Align, Case, Part, Placeholder, Source, Template},
+  theme::theme,
+  time::TimeDisplay,
+};
+
+/// Indentation of the further lines of multi-line messages.
+const INDENT: &str = "    ";
+
+/// Spaces to pad fields with, a slice at a time.
+const SPACES: &str = "                                ";
+
+/// Turns parsed records into colored, human-readable lines.
+#[derive(Debug, Clone)]
+pub struct Renderer {
+  levels: Levels,
+  template: Template,
+  meta_mode: MetaMode,
+  time: TimeDisplay,
+  /// The line being rendered, kept to reuse its allocation.
+  line: String,
+  /// The text of the placeholder being rendered, likewise.
+  field: String,
+}
+
+impl Renderer {
+  pub fn new(levels: Levels, template: Template) -> Self {
+    Renderer {
+      levels,
+      template,
+      meta_mode: MetaMode::default(),
+      time: TimeDisplay::default(),
+      line: String::new(),
+      field: String::new(),
+    }
+  }
+
+  /// Sets how timestamps are shown.
+  pub fn time_display(mut self, time: TimeDisplay) -> Self {
+    self.time = time;
+    self
+  }
+
+  /// Sets how the fields in `{meta}` are shown.
+  pub fn meta_mode(mut self, mode: MetaMode) -> Self {
+    self.meta_mode = mode;
+    self
+  }
+
+  /// Writes `record` as a single line laid out by the template. By default
+  /// this is in the spirit of Winston's `format.simple()`:
+  /// `timestamp level: message {meta}`.
+  pub fn render<W: Write>(&mut self, record: &Record, out: &mut W) -> io::Result<()> {
+    let mut line = std::mem::take(&mut self.line);
+    let mut field = std::mem::take(&mut self.field);
+    line.clear();
+    // Placeholders that come out empty take the whitespace between them and
+    // the rest of the line with them, like the space after a missing
+    // `{timestamp}`, so that a missing field in the middle of the line does
+    // not leave two spaces behind.
+    let mut shown_end = None;
+    let mut empty_end = None;
+    for part in &self.template.parts {
+      match part {
+        Part::Literal(text) => line.push_str(text),
+        Part::Field(placeholder) => {
+          let start = line.len();
+          self.render_field(record, placeholder, &mut line, &mut field);
+          let shown = line.len() > start;
+          if let Some(end) = empty_end {
+            if shown_end.is_none() || line[..end].ends_with(char::is_whitespace) {
+              let separator = &line[end..start];
+              let len = separator.len() - separator.trim_start().len();
+              line.replace_range(end..end + len, "");
+            }
+          }
+          if shown {
+            shown_end = Some(line.len());
+            empty_end = None;
+          } else {
+            empty_end = Some(line.len());
+          }
+        }
+      }
+    }
+    if let Some(end) = empty_end {
+      let start = shown_end.unwrap_or(0);
+      let kept = start + line[start..end].trim_end().len();
+      line.replace_range(kept..end, "");
+    }
+    line.push('\n');
+    let written = out.write_all(line.as_bytes());
+    self.line = line;
+    self.field = field;
+    written?;
+    if let Some(rest) = self.message_rest(record) {
+      for line in rest.lines() {
+        self.render_continuation(line, out)?;
+      }
+    }
+    if self.meta_mode == MetaMode::Expanded {
+      meta::expanded(self.meta_fields(record), out)?;
+    }
+    if let Some(stack) = self.stack(record) {
+      render_stack(stack, out)?;
+    }
+    Ok(())
+  }
+
+  /// Writes a line that continues the message of the record written last.
+  pub fn render_continuation<W: Write>(&self, line: &str, out: &mut W) -> io::Result<()> {
+    out.write_all(INDENT.as_bytes())?;
+    out.write_all(line.as_bytes())?;
+    out.write_all(b"\n")
+  }
+
+  /// The lines of a multi-line message after the first, which are printed
+  /// below the line rather than breaking up the template.
+  fn message_rest<'a>(&self, record: &'a Record) -> Option<&'a str> {
+    let shown = self.template.parts.iter().any(|part| {
+      matches!(part, Part::Field(placeholder) if matches!(placeholder.source, Source::Message))
+    });
+    if !shown {
+      return None;
+    }
+    let (_, rest) = record.get_str("message")?.split_once('\n')?;
+    Some(rest)
+  }
+
+  /// The stack trace to print below the line, unless the template shows it.
+  fn stack<'a>(&self, record: &'a Record) -> Option<&'a str> {
+    if !self.template.is_meta("stack") {
+      return None;
+    }
+    record.get_str("stack")
+  }
+
+  fn render_field(
+    &self,
+    record: &Record,
+    placeholder: &Placeholder,
+    line: &mut String,
+    field: &mut String,
+  ) {
+    let (text, style) = match &placeholder.source {
+      Source::Level => {
+        let level = record.level().unwrap_or_default();
+        let style = self.levels.style(&level);
+        (level, style)
+      }
+      Source::Timestamp => {
+        let text = match record.get_str("timestamp") {
+          Some(timestamp) if self.time.is_original() => Cow::Borrowed(timestamp),
+          _ => record
+            .timestamp()
+            .map(|value| self.time.render(value))
+            .unwrap_or_default(),
+        };
+        (text, theme().timestamp)
+      }
+      Source::Message => {
+        let text = match record.get_str("message") {
+          Some(message) => Cow::Borrowed(message.lines().next().unwrap_or_default()),
+          None => field_text(record.message()),
+        };
+        (text, theme().message)
+      }
+      Source::Meta => {
+        self.write_meta(record, placeholder.style, line);
+        return;
+      }
+      Source::Field(path) => (field_text(record.lookup(path)), Style::new()),
+    };
+    field.clear();
+    format_text(&text, placeholder, field);
+    if !field.is_empty() {
+      line.push_styled(field, style.patch(placeholder.style));
+    }
+  }
+
+  /// The fields that are not shown by any other placeholder.
+  fn meta_fields<'r, 'a>(
+    &'r self,
+    record: &'r Record<'a>,
+  ) -> impl Iterator<Item = (&'r str, &'r Field<'a>)> {
+    record
+      .meta()
+      .filter(|(key, field)| self.template.is_meta(key) && !(*key == "stack" && field.is_string()))
+  }
+
+  /// Writes the text of `{meta}` on the log line.
+  fn write_meta(&self, record: &Record, style: Style, line: &mut String) {
+    let mut fields = self.meta_fields(record).peekable();
+    if fields.peek().is_none() {
+      return;
+    }
+    match self.meta_mode {
+      MetaMode::Inline => meta::write_inline(line, fields, style),
+      MetaMode::Json => meta::write_json(line, fields, style),
+      MetaMode::Expanded | MetaMode::Hidden => {}
+    }
+  }
+}
+
+/// Appends `text` to `out` with the case, truncation and padding of
+/// `placeholder` applied.
+fn format_text(text: &str, placeholder: &Placeholder, out: &mut String) {
+  let start = out.len();
+  match placeholder.case {
+    Some(Case::Upper) => out.extend(text.chars().flat_map(char::to_uppercase)),
+    // How a sigma is lowercased depends on whether it ends a word.
+    Some(Case::Lower) if !text.contains('Σ') => {
+      out.extend(text.chars().flat_map(char::to_lowercase))
+    }
+    Some(Case::Lower) => out.push_str(&text.to_lowercase()),
+    None => out.push_str(text),
+  }
+  let mut len = out[start..].chars().count();
+  if let Some(max) = placeholder.truncate {
+    if len > max {
+      let end = match max.checked_sub(1) {
+        Some(kept) => out[start..]
+          .char_indices()
+          .nth(kept)
+          .map_or(out.len(), |(i, _)| start + i),
+        None => start,
+      };
+      out.truncate(end);
+      if max > 0 {
+        out.push('…');
+      }
+      len = max;
+    }
+  }
+  let Some((align, width)) = placeholder.pad else {
+    return;
+  };
+  let mut missing = width.saturating_sub(len);
+  while missing > 0 {
+    let spaces = &SPACES[..missing.min(SPACES.len())];
+    match align {
+      Align::Left => out.push_str(spaces),
+      Align::Right => out.insert_str(start, spaces),
+    }
+    missing -= spaces.len();
+  }
+}
+
+/// Strings are shown without quotes, everything else as compact JSON. Missing
+/// fields are empty.
+pub(crate) fn field_text(value: Option<&Value>) -> Cow<'_, str> {
+  match value {
+    Some(Value::String(s)) => Cow::Borrowed(s),
+    Some(other) => Cow::Owned(other.to_string()),
+    None => Cow::Borrowed(""),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{level::Levels, record::strip_ansi};
+
+  fn placeholder(spec: &str) -> Placeholder {
+    match format!("{{message:{spec}}}")
+      .parse::<Template>()
+      .unwrap()
+      .parts
+      .remove(0)
+    {
+      Part::Field(placeholder) => placeholder,
+      Part::Literal(_) => unreachable!(),
+    }
+  }
+
+  fn format(text: &str, spec: &str) -> String {
+    let mut out = String::new();
+    format_text(text, &placeholder(spec), &mut out);
+    out
+  }
+
+  fn render(template: &str, line: &str) -> String {
+    let mut renderer = Renderer::new(Levels::npm(), template.parse().unwrap());
+    let mut out = Vec::new();
+    renderer
+      .render(&Record::parse(line.as_bytes()).unwrap(), &mut out)
+      .unwrap();
+    strip_ansi(&String::from_utf8(out).unwrap())
+  }
+
+  #[test]
+  fn format_text_modifiers() {
+    assert_eq!(format("info", "pad7"), "info   ");
+    assert_eq!(format("info", "lpad7"), "   info");
+    assert_eq!(format("warning", "pad3"), "warning");
+    assert_eq!(format("info", "upper:pad5"), "INFO ");
+    assert_eq!(format("WARN", "lower"), "warn");
+    assert_eq!(format("timeout", "trunc4"), "tim…");
+    assert_eq!(format("time", "trunc4"), "time");
+    assert_eq!(format("timeout", "trunc1"), "…");
+    assert_eq!(format("timeout", "trunc0"), "");
+    assert_eq!(format("naïve", "trunc3:lpad4"), " na…");
+    assert_eq!(format("", "pad3"), "   ");
+    assert_eq!(format("a", "lpad40"), format!("{}a", " ".repeat(39)));
+  }
+
+  #[test]
+  fn padding_is_kept() {
+    let line = r#"{"level":"info","message":"hello"}"#;
+    assert_eq!(render("{level:lpad7} {message}", line), "   info hello\n");
+    assert_eq!(render("{message} {level:pad7}", line), "hello info   \n");
+    assert_eq!(render("  {message}", line), "  hello\n");
+  }
+
+  #[test]
+  fn empty_placeholders_drop_their_separators() {
+    let line = r#"{"level":"info","message":"hello"}"#;
+    assert_eq!(
+      render("{timestamp} {level}: {message} {meta}", line),
+      "info: hello\n"
+    );
+    assert_eq!(
+      render("{timestamp} [{level}] {message}", line),
+      "[info] hello\n"
+    );
+    assert_eq!(
+      render("{label} {level} {service} {message}", line),
+      "info hello\n"
+    );
+    assert_eq!(
+      render("{level} {label} {service} {message}", line),
+      "info hello\n"
+    );
+    assert_eq!(render("{label} {service} {message}", line), "hello\n");
+    assert_eq!(
+      render("{level:pad7} {service} {message}", line),
+      "info    hello\n"
+    );
+    assert_eq!(render("{label} {service}", line), "\n");
+    assert_eq!(
+      render(
+        "{timestamp} {level}: {message} {meta}",
+        r#"{"level":"info","message":"hello","a":1}"#
+      ),
+      "info: hello a=1\n"
+    );
+  }
+}
@@ -0,0 +1,559 @@
+//! Reading inputs backward from their end, to show the newest records first
+//! or only the last few.
+//!
+//! Regular files are scanned backward through their memory mapping, or read
+//! backward in blocks if they are not mapped, so the last records of a huge
+//! file are found without reading the rest of it. Anything else, like stdin
+//! or compressed files, is read into memory first.
+
+use std::{
+  borrow::Cow,
+  fs::File,
+  io::{self, Read, Seek, SeekFrom},
+  iter,
+};
+
+use anyhow::Context;
+use jiff::Timestamp;
+
+use crate::{
+  filter::Filter,
+  input::{mmap::Mapped, trim_newline, Input, LineSink, OpenOptions},
+  record::Record,
+  time::parse_timestamp,
+};
+
+/// Files that are not mapped are read in blocks of this many bytes.
+#[cfg(not(test))]
+const BLOCK_SIZE: usize = crate::input::BUFFER_SIZE;
+/// Small in tests, so that a few lines take several blocks.
+#[cfg(test)]
+const BLOCK_SIZE: usize = 16;
+
+/// How to read inputs backward.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Backward {
+  /// Interleave several inputs by timestamp rather than concatenating them.
+  pub merge: bool,
+  /// Only show this many of the last records that match the filter.
+  pub count: Option<usize>,
+  /// Show the newest records first.
+  pub reverse: bool,
+  /// Whether the sink shows the lines that are not JSON below a record the
+  /// filter hides, which then have to be kept while looking for the last
+  /// records.
+  pub hidden_lines: bool,
+}
+
+/// Feeds the last records of `inputs` that `filter` lets through to `sink`,
+/// or all of them newest first, as chosen with `backward`.
+///
+/// Lines that are not JSON stay below the record before them, and are fed to
+/// `sink` along with it. They do not count as records of their own.
+pub fn read_backward<S: LineSink>(
+  inputs: &[Input],
+  options: &OpenOptions,
+  filter: &Filter,
+  backward: Backward,
+  sink: &mut S,
+) -> anyhow::Result<()> {
+  let mut contents = inputs
+    .iter()
+    .map(|input| Contents::open(input, options))
+    .collect::<anyhow::Result<Vec<_>>>()?;
+  let streams: Vec<Entries> = contents
+    .iter_mut()
+    .enumerate()
+    .map(|(source, contents)| Entries::new(contents.lines(), source))
+    .collect();
+  let entries: Box<dyn Iterator<Item = io::Result<Entry>>> = if backward.merge && streams.len() > 1
+  {
+    Box::new(newest_first(streams)?)
+  } else {
+    Box::new(streams.into_iter().rev().flatten())
+  };
+
+  let Some(count) = backward.count else {
+    for entry in entries {
+      entry?.write(sink)?;
+    }
+    return Ok(sink.idle()?);
+  };
+  let mut tail = last_matching(entries, filter, count, backward.hidden_lines)?;
+  if !backward.reverse {
+    tail.reverse();
+  }
+  for entry in tail {
+    entry.write(sink)?;
+  }
+  Ok(sink.idle()?)
+}
+
+/// Takes `entries` up to the `count`th one whose record `filter` lets
+/// through. Of the other entries, only those are kept that have lines to
+/// show: lines before the first record, or with `hidden_lines`, the lines
+/// below a record that is hidden but inside the time window.
+fn last_matching<'a>(
+  entries: impl Iterator<Item = io::Result<Entry<'a>>>,
+  filter: &Filter,
+  count: usize,
+  hidden_lines: bool,
+) -> io::Result<Vec<Entry<'a>>> {
+  let mut tail = Vec::new();
+  let mut matched = 0;
+  for entry in entries {
+    if matched == count {
+      break;
+    }
+    let entry = entry?;
+    match &entry.record() {
+      Some(record) if filter.matches(record) => matched += 1,
+      Some(record) => {
+        let shows_lines = hidden_lines && entry.lines.len() > 1 && filter.in_time_window(record);
+        if !shows_lines {
+          continue;
+        }
+      }
+      None => {}
+    }
+    tail.push(entry);
+  }
+  Ok(tail)
+}
+
+/// The contents of an input that are read backward.
+enum Contents {
+  Mapped(Mapped),
+  File(Blocks),
+  Read(Vec<u8>),
+}
+
+impl Contents {
+  fn open(input: &Input, options: &OpenOptions) -> anyhow::Result<Contents> {
+    if let Some(mapped) = input.map(options)? {
+      return Ok(Contents::Mapped(mapped));
+    }
+    if let Some(file) = input.plain_file(options)? {
+      let blocks = Blocks::new(file).with_context(|| format!("failed to read {}", input.name()))?;
+      return Ok(Contents::File(blocks));
+    }
+    let mut data = Vec::new();
+    input
+      .open(options)?
+      .read_to_end(&mut data)
+      .with_context(|| format!("failed to read {}", input.name()))?;
+    Ok(Contents::Read(data))
+  }
+
+  fn lines(&mut self) -> Lines<'_> {
+    match self {
+      Contents::Mapped(mapped) => Lines::Memory(mapped.rest()),
+      Contents::File(blocks) => Lines::File(blocks),
+      Contents::Read(data) => Lines::Memory(data),
+    }
+  }
+}
+
+/// A regular file that is read backward in blocks, holding on to no more of
+/// it than the block and line at hand.
+struct Blocks {
+  file: File,
+  /// Where reading stops, the start of the file or the position it was
+  /// opened at.
+  start: u64,
+  /// The offset of `buf` in the file.
+  pos: u64,
+  /// What has been read of the file from `pos` on but not taken yet.
+  buf: Vec<u8>,
+}
+
+impl Blocks {
+  /// Reads `file` backward from its end to where it is positioned.
+  fn new(mut file: File) -> io::Result<Blocks> {
+    let start = file.stream_position()?;
+    let end = file.metadata()?.len();
+    Ok(Blocks {
+      file,
+      start,
+      pos: end.max(start),
+      buf: Vec::new(),
+    })
+  }
+
+  /// Takes the last line off the file.
+  fn pop_line(&mut self) -> io::Result<Option<Vec<u8>>> {
+    loop {
+      let body = self.buf.strip_suffix(b"\n").unwrap_or(&self.buf);
+      let start = match memchr::memrchr(b'\n', body) {
+        Some(end) => end + 1,
+        // The line may go on in the block before.
+        None if self.pos > self.start => {
+          self.read_block()?;
+          continue;
+        }
+        None if self.buf.is_empty() => return Ok(None),
+        None => 0,
+      };
+      let line = trim_newline(&body[start..]).to_vec();
+      self.buf.truncate(start);
+      return Ok(Some(line));
+    }
+  }
+
+  /// Reads the block before `buf` and puts it in front of it.
+  fn read_block(&mut self) -> io::Result<()> {
+    let len = (self.pos - self.start).min(BLOCK_SIZE as u64) as usize;
+    self.pos -= len as u64;
+    let mut block = vec![0; len + self.buf.len()];
+    self.file.seek(SeekFrom::Start(self.pos))?;
+    self.file.read_exact(&mut block[..len])?;
+    block[len..].copy_from_slice(&self.buf);
+    self.buf = block;
+    Ok(())
+  }
+}
+
+/// The lines of an input, taken off its end one by one.
+enum Lines<'a> {
+  /// The part of an input in memory that has not been taken yet.
+  Memory(&'a [u8]),
+  File(&'a mut Blocks),
+}
+
+impl<'a> Lines<'a> {
+  /// Takes the last line off the input.
+  fn pop(&mut self) -> io::Result<Option<Cow<'a, [u8]>>> {
+    match self {
+      Lines::Memory(data) => {
+        if data.is_empty() {
+          return Ok(None);
+        }
+        let body = data.strip_suffix(b"\n").unwrap_or(data);
+        let start = memchr::memrchr(b'\n', body).map_or(0, |end| end + 1);
+        *data = &data[..start];
+        Ok(Some(Cow::Borrowed(trim_newline(&body[start..]))))
+      }
+      Lines::File(blocks) => Ok(blocks.pop_line()?.map(Cow::Owned)),
+    }
+  }
+}
+
+/// A record with the lines that are not JSON below it. The lines before the
+/// first record of an input make up an entry without one.
+struct Entry<'a> {
+  source: usize,
+  /// Whether the first line is a record.
+  has_record: bool,
+  /// All lines of the entry, starting with the record's.
+  lines: Vec<Cow<'a, [u8]>>,
+}
+
+impl Entry<'_> {
+  /// The record of the entry, parsed again from its line.
+  fn record(&self) -> Option<Record<'_>> {
+    if self.has_record {
+      Record::parse(&self.lines[0])
+    } else {
+      None
+    }
+  }
+
+  fn write<S: LineSink>(&self, sink: &mut S) -> io::Result<()> {
+    for line in &self.lines {
+      sink.line(self.source, line)?;
+    }
+    Ok(())
+  }
+}
+
+/// The entries of an input, from the last to the first.
+struct Entries<'a> {
+  lines: Lines<'a>,
+  source: usize,
+  /// The timestamp of the entry read last, which entries without one are
+  /// sorted by, so that they stay next to it when merging.
+  key: Timestamp,
+}
+
+impl<'a> Entries<'a> {
+  fn new(lines: Lines<'a>, source: usize) -> Self {
+    Entries {
+      lines,
+      source,
+      key: Timestamp::MAX,
+    }
+  }
+
+  fn next_entry(&mut self) -> io::Result<Option<Entry<'a>>> {
+    let mut lines = Vec::new();
+    let mut has_record = false;
+    while let Some(line) = self.lines.pop()? {
+      has_record = Record::parse(&line).is_some();
+      lines.push(line);
+      if has_record {
+        break;
+      }
+    }
+    if lines.is_empty() {
+      return Ok(None);
+    }
+    lines.reverse();
+    Ok(Some(Entry {
+      source: self.source,
+      has_record,
+      lines,
+    }))
+  }
+
+  /// The next entry along with the timestamp it is sorted by.
+  fn next_keyed(&mut self) -> io::Result<Option<(Timestamp, Entry<'a>)>> {
+    let Some(entry) = self.next_entry()? else {
+      return Ok(None);
+    };
+    if let Some(timestamp) = entry
+      .record()
+      .as_ref()
+      .and_then(Record::timestamp)
+      .and_then(parse_timestamp)
+    {
+      self.key = timestamp;
+    }
+    Ok(Some((self.key, entry)))
+  }
+}
+
+impl<'a> Iterator for Entries<'a> {
+  type Item = io::Result<Entry<'a>>;
+
+  fn next(&mut self) -> Option<io::Result<Entry<'a>>> {
+    self.next_entry().transpose()
+  }
+}
+
+/// Interleaves the entries of several inputs, newest first. Of entries with
+/// equal timestamps, those of later inputs come first, the reverse of
+/// [`merge`](crate::input::merge::merge).
+fn newest_first(mut streams: Vec<Entries>) -> io::Result<impl Iterator<Item = io::Result<Entry>>> {
+  let mut heads = streams
+    .iter_mut()
+    .map(Entries::next_keyed)
+    .collect::<io::Result<Vec<_>>>()?;
+  Ok(iter::from_fn(move || {
+    let (newest, _) = heads
+      .iter()
+      .enumerate()
+      .filter_map(|(source, head)| Some((source, head.as_ref()?.0)))
+      .max_by_key(|&(source, key)| (key, source))?;
+    let (_, entry) = heads[newest].take()?;
+    match streams[newest].next_keyed() {
+      Ok(head) => heads[newest] = head,
+      Err(err) => return Some(Err(err)),
+    }
+    Some(Ok(entry))
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Entries as their source and lines, in the order they come.
+  fn lines<'a>(
+    entries: impl IntoIterator<Item = io::Result<Entry<'a>>>,
+  ) -> Vec<(usize, Vec<String>)> {
+    entries
+      .into_iter()
+      .map(|entry| {
+        let entry = entry.unwrap();
+        let lines = entry
+          .lines
+          .iter()
+          .map(|line| String::from_utf8(line.to_vec()).unwrap())
+          .collect();
+        (entry.source, lines)
+      })
+      .collect()
+  }
+
+  fn owned(entries: &[(usize, &[&str])]) -> Vec<(usize, Vec<String>)> {
+    entries
+      .iter()
+      .map(|(source, lines)| (*source, lines.iter().map(|&line| line.to_owned()).collect()))
+      .collect()
+  }
+
+  fn memory(data: &str, source: usize) -> Entries<'_> {
+    Entries::new(Lines::Memory(data.as_bytes()), source)
+  }
+
+  fn record(message: &str, second: u32) -> String {
+    format!(r#"{{"message":"{message}","timestamp":"2026-10-16T10:00:{second:02}Z"}}"#)
+  }
+
+  #[test]
+  fn entries_from_the_end() {
+    let a = record("a", 1);
+    let b = record("b", 2);
+    let expected = owned(&[(0, &[&b, "  at b"]), (0, &[&a])]);
+    let with_newline = format!("{a}\n{b}\n  at b\n");
+    assert_eq!(lines(memory(&with_newline, 0)), expected);
+    let without_newline = format!("{a}\n{b}\n  at b");
+    assert_eq!(lines(memory(&without_newline, 0)), expected);
+    let crlf = format!("{a}\r\n{b}\r\n  at b\r\n");
+    assert_eq!(lines(memory(&crlf, 0)), expected);
+    assert!(memory("", 0).next().is_none());
+  }
+
+  #[test]
+  fn lines_before_the_first_record() {
+    let a = record("a", 1);
+    let data = format!("> api@1.0.0 start\n\n{a}\nnot json\n");
+    let entries: Vec<Entry> = memory(&data, 3).map(Result::unwrap).collect();
+    assert_eq!(
+      lines(memory(&data, 3)),
+      owned(&[(3, &[&a, "not json"]), (3, &["> api@1.0.0 start", ""])])
+    );
+    assert!(entries[0].record().is_some());
+    assert!(entries[1].record().is_none());
+    assert_eq!(
+      lines(memory("only\nnot json\n", 0)),
+      owned(&[(0, &["only", "not json"])])
+    );
+  }
+
+  #[test]
+  fn files_are_read_backward_in_blocks() {
+    let long = format!("  at {}", "x".repeat(3 * BLOCK_SIZE));
+    let data = [
+      "banner",
+      "",
+      &record("a", 1),
+      &long,
+      &record("b", 2),
+      "  at b",
+      "",
+      &record("c", 3),
+    ]
+    .join("\n");
+    let path = std::env::temp_dir().join(format!("winstonjson-reverse-{}.log", std::process::id()));
+    for data in [
+      data.clone(),
+      format!("{data}\n"),
+      data.replace('\n', "\r\n"),
+    ] {
+      std::fs::write(&path, &data).unwrap();
+      // From the start, and from the start of each line.
+      let starts = iter::once(0).chain(data.match_indices('\n').map(|(end, _)| end + 1));
+      for start in starts {
+        let mut file = File::open(&path).unwrap();
+        file.seek(SeekFrom::Start(start as u64)).unwrap();
+        let mut blocks = Blocks::new(file).unwrap();
+        assert_eq!(
+          lines(Entries::new(Lines::File(&mut blocks), 0)),
+          lines(memory(&data[start..], 0)),
+          "from {start} of {data:?}"
+        );
+        assert!(blocks.buf.is_empty());
+      }
+    }
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn newest_first_across_inputs() {
+    let first = [
+      record("first 1", 1),
+      record("first 3", 3),
+      record("first 5", 5),
+    ]
+    .join("\n");
+    let second = format!(
+      "{}\n{}\nno timestamp\n{}\n",
+      record("second 3", 3),
+      r#"{"message":"second untimed"}"#,
+      record("second 4", 4)
+    );
+    let streams = vec![memory(&first, 0), memory(&second, 1)];
+    let order: Vec<(usize, String)> = newest_first(streams)
+      .unwrap()
+      .map(|entry| {
+        let entry = entry.unwrap();
+        let message = entry.record().unwrap().message().unwrap().clone();
+        (entry.source, message.as_str().unwrap().to_owned())
+      })
+      .collect();
+    assert_eq!(
+      order,
+      [
+        (0, "first 5"),
+        (1, "second 4"),
+        // Sorted like the record after it, and ties go to the later input.
+        (1, "second untimed"),
+        (1, "second 3"),
+        (0, "first 3"),
+        (0, "first 1"),
+      ]
+      .map(|(source, message)| (source, message.to_owned()))
+    );
+  }
+
+  #[test]
+  fn tail_keeps_only_lines_that_are_shown() {
+    let data = [
+      "banner",
+      &record("shown 1", 1),
+      "  at shown 1",
+      &record("hidden 2", 2),
+      "  at hidden 2",
+      &record("hidden 3", 3),
+      &record("shown 4", 4),
+      &record("hidden 5", 5),
+      "  at hidden 5",
+    ]
+    .join("\n");
+    // The lines of the kept entries in input order, records by their message.
+    let tail = |filter: &Filter, count, hidden_lines| {
+      let mut tail = last_matching(memory(&data, 0), filter, count, hidden_lines).unwrap();
+      tail.reverse();
+      lines(tail.into_iter().map(Ok))
+        .into_iter()
+        .flat_map(|(_, lines)| lines)
+        .map(|line| line.split('"').nth(3).unwrap_or(&line).to_owned())
+        .collect::<Vec<_>>()
+    };
+    let filter = Filter::new().expr("message ~ /shown/").unwrap();
+    assert_eq!(tail(&filter, 1, false), ["shown 4"]);
+    assert_eq!(
+      tail(&filter, 1, true),
+      ["shown 4", "hidden 5", "  at hidden 5"]
+    );
+    assert_eq!(
+      tail(&filter, 2, false),
+      ["shown 1", "  at shown 1", "shown 4"]
+    );
+    assert_eq!(
+      tail(&filter, 5, true),
+      [
+        "banner",
+        "shown 1",
+        "  at shown 1",
+        "hidden 2",
+        "  at hidden 2",
+        "shown 4",
+        "hidden 5",
+        "  at hidden 5",
+      ]
+    );
+
+    // Nothing is shown of records outside the time window.
+    let since = "2026-10-16T10:00:04Z".parse().ok();
+    let filter = Filter::new()
+      .expr("message ~ /shown/")
+      .unwrap()
+      .time_window(since, None);
+    assert_eq!(
+      tail(&filter, 5, true),
+      ["banner", "shown 4", "hidden 5", "  at hidden 5"]
+    );
+  }
+}
@@ -0,0 +1,181 @@
+//! Rendering of the `stack` field that `winston.format.errors({ stack: true })`
+//! adds to records.
+
+use std::io::{self, Write};
+
+use crate::{
+  style::{Style, StyledOut},
+  theme::theme,
+};
+
+/// Indentation of stack lines below the log line.
+const INDENT: &str = "    ";
+
+/// What a line of a V8 stack trace refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frame {
+  /// The `Error: message` line, or a continuation of it.
+  Header,
+  /// A frame in the application's own code.
+  Own,
+  /// A frame inside `node_modules`.
+  Dependency,
+  /// A frame inside Node.js itself, e.g. `node:internal/...`.
+  Internal,
+}
+
+impl Frame {
+  pub fn classify(line: &str) -> Frame {
+    let line = line.trim_start();
+    if !line.starts_with("at ") {
+      Frame::Header
+    } else if line.contains("node:") || line.contains("(internal/") || line.contains(" internal/") {
+      Frame::Internal
+    } else if line.contains("node_modules") {
+      Frame::Dependency
+    } else {
+      Frame::Own
+    }
+  }
+}
+
+/// Writes `stack` indented below a log line, one line per frame, with frames
+/// from dependencies and Node.js internals dimmed.
+pub fn render_stack<W: Write>(stack: &str, out: &mut W) -> io::Result<()> {
+  let mut text = String::new();
+  write_stack(&mut text, stack);
+  out.write_all(text.as_bytes())
+}
+
+/// Like [`render_stack`], but writes to any [`StyledOut`].
+pub fn write_stack<O: StyledOut>(out: &mut O, stack: &str) {
+  // Stacks that were JSON-encoded twice still contain literal `\n`s.
+  let unescaped;
+  let stack = if !stack.contains('\n') && stack.contains("\\n") {
+    unescaped = stack.replace("\\n", "\n");
+    &unescaped
+  } else {
+    stack
+  };
+  for line in stack.lines() {
+    let line = line.trim_end();
+    out.push_styled(INDENT, Style::new());
+    let kind = Frame::classify(line);
+    if kind == Frame::Header {
+      out.push_styled(line.trim(), theme().stack_header);
+    } else {
+      out.push_styled("  ", Style::new());
+      let frame = line.trim_start();
+      match kind {
+        Frame::Dependency => out.push_styled(frame, theme().stack_dependency),
+        Frame::Internal => out.push_styled(frame, theme().stack_internal),
+        _ => push_location(out, frame),
+      }
+    }
+    out.push_styled("\n", Style::new());
+  }
+}
+
+/// Colors the `file:line:column` part of a frame such as
+/// `at handler (/app/src/routes.js:10:5)`.
+fn push_location<O: StyledOut>(out: &mut O, frame: &str) {
+  let (head, location, tail) = match frame.rfind('(') {
+    Some(open) if frame.ends_with(')') => (&frame[..=open], &frame[open + 1..frame.len() - 1], ")"),
+    _ => match frame.strip_prefix("at ") {
+      Some(location) => ("at ", location, ""),
+      None => return out.push_styled(frame, Style::new()),
+    },
+  };
+  out.push_styled(head, Style::new());
+  out.push_styled(location, theme().stack_location);
+  out.push_styled(tail, Style::new());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Collects what is written along with its style.
+  #[derive(Default)]
+  struct Pieces(Vec<(String, Style)>);
+
+  impl StyledOut for Pieces {
+    fn push_styled(&mut self, text: &str, style: Style) {
+      self.0.push((text.to_owned(), style));
+    }
+  }
+
+  impl Pieces {
+    fn text(&self) -> String {
+      self.0.iter().map(|(text, _)| text.as_str()).collect()
+    }
+
+    fn style_of(&self, text: &str) -> Option<Style> {
+      self
+        .0
+        .iter()
+        .find(|(piece, _)| piece == text)
+        .map(|(_, style)| *style)
+    }
+  }
+
+  #[test]
+  fn frames_are_classified() {
+    let cases = [
+      ("Error: connect ECONNREFUSED", Frame::Header),
+      (
+        "    at TCPConnectWrap.afterConnect [as oncomplete] (node:net:1555:16)",
+        Frame::Internal,
+      ),
+      (
+        "    at process.processTicksAndRejections (internal/process/task_queues.js:95:5)",
+        Frame::Internal,
+      ),
+      (
+        "    at internal/main/run_main_module.js:17:47",
+        Frame::Internal,
+      ),
+      (
+        "    at Layer.handle (/app/node_modules/express/lib/router/layer.js:95:5)",
+        Frame::Dependency,
+      ),
+      ("    at handler (/app/src/routes.js:10:5)", Frame::Own),
+      ("    at /app/src/index.js:3:1", Frame::Own),
+      ("  caused by a timeout", Frame::Header),
+    ];
+    for (line, frame) in cases {
+      assert_eq!(Frame::classify(line), frame, "{line}");
+    }
+  }
+
+  #[test]
+  fn escaped_newlines_are_unescaped() {
+    let mut out = Pieces::default();
+    write_stack(
+      &mut out,
+      r"Error: boom\n    at handler (/app/src/routes.js:10:5)",
+    );
+    assert_eq!(
+      out.text(),
+      "    Error: boom\n      at handler (/app/src/routes.js:10:5)\n"
+    );
+    assert_eq!(
+      out.style_of("/app/src/routes.js:10:5"),
+      Some(theme().stack_location)
+    );
+  }
+
+  #[test]
+  fn dependency_and_internal_frames_are_dimmed() {
+    let dependency = "at Layer.handle (/app/node_modules/express/lib/router/layer.js:95:5)";
+    let internal = "at process.processTicksAndRejections (node:internal/process/task_queues:95:5)";
+    let mut out = Pieces::default();
+    write_stack(
+      &mut out,
+      &format!("Error: boom\n    {dependency}\n    {internal}\n"),
+    );
+    assert_eq!(out.style_of("Error: boom"), Some(theme().stack_header));
+    assert_eq!(out.style_of(dependency), Some(theme().stack_dependency));
+    assert_eq!(out.style_of(internal), Some(theme().stack_internal));
+  }
+}
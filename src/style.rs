@@ -0,0 +1,429 @@
+use std::{fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer};
+
+use crate::color::{ansi256_to_rgb, color_level, rgb_to_ansi256, rgb_to_basic, ColorLevel};
+
+/// One of the 16 basic ANSI terminal colors, a color of the 256-color palette
+/// or an RGB color. Colors the terminal lacks are replaced with the closest
+/// one it has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+  Black,
+  Red,
+  Green,
+  Yellow,
+  Blue,
+  Magenta,
+  Cyan,
+  White,
+  Gray,
+  BrightRed,
+  BrightGreen,
+  BrightYellow,
+  BrightBlue,
+  BrightMagenta,
+  BrightCyan,
+  BrightWhite,
+  Fixed(u8),
+  Rgb(u8, u8, u8),
+}
+
+impl Color {
+  /// The basic colors in the order of their palette indices.
+  const BASIC: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+    Color::Gray,
+    Color::BrightRed,
+    Color::BrightGreen,
+    Color::BrightYellow,
+    Color::BrightBlue,
+    Color::BrightMagenta,
+    Color::BrightCyan,
+    Color::BrightWhite,
+  ];
+
+  /// The closest color available at `level`.
+  pub fn degrade(self, level: ColorLevel) -> Color {
+    match (self, level) {
+      (Color::Rgb(r, g, b), ColorLevel::Ansi256) => Color::Fixed(rgb_to_ansi256((r, g, b))),
+      (Color::Rgb(r, g, b), ColorLevel::Basic | ColorLevel::None) => {
+        Color::BASIC[rgb_to_basic((r, g, b)) as usize]
+      }
+      (Color::Fixed(index), ColorLevel::Basic | ColorLevel::None) => {
+        Color::BASIC[rgb_to_basic(ansi256_to_rgb(index)) as usize]
+      }
+      _ => self,
+    }
+  }
+
+  /// The SGR parameters selecting the color as foreground (`base` 30) or
+  /// background (`base` 40).
+  fn write_code(self, base: u8, f: &mut impl fmt::Write) -> fmt::Result {
+    match self {
+      Color::Fixed(index) => write!(f, "{};5;{index}", base + 8),
+      Color::Rgb(r, g, b) => write!(f, "{};2;{r};{g};{b}", base + 8),
+      basic => write!(f, "{}", basic.basic_code() - 30 + base),
+    }
+  }
+
+  fn basic_code(self) -> u8 {
+    match self {
+      Color::Black => 30,
+      Color::Red => 31,
+      Color::Green => 32,
+      Color::Yellow => 33,
+      Color::Blue => 34,
+      Color::Magenta => 35,
+      Color::Cyan => 36,
+      Color::White => 37,
+      Color::Gray => 90,
+      Color::BrightRed => 91,
+      Color::BrightGreen => 92,
+      Color::BrightYellow => 93,
+      Color::BrightBlue => 94,
+      Color::BrightMagenta => 95,
+      Color::BrightCyan => 96,
+      Color::BrightWhite => 97,
+      Color::Fixed(_) | Color::Rgb(..) => 39,
+    }
+  }
+
+  /// Looks up a color by the name the `colors` package (and therefore
+  /// `winston.addColors()`) uses for it, e.g. `brightRed` or `grey`, or an RGB
+  /// color written as `#rrggbb`.
+  fn from_name(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+      let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+      return match hex.len() {
+        6 => Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?)),
+        _ => None,
+      };
+    }
+    Some(match name {
+      "black" => Color::Black,
+      "red" => Color::Red,
+      "green" => Color::Green,
+      "yellow" => Color::Yellow,
+      "blue" => Color::Blue,
+      "magenta" => Color::Magenta,
+      "cyan" => Color::Cyan,
+      "white" => Color::White,
+      "gray" | "grey" | "brightBlack" => Color::Gray,
+      "brightRed" => Color::BrightRed,
+      "brightGreen" => Color::BrightGreen,
+      "brightYellow" => Color::BrightYellow,
+      "brightBlue" => Color::BrightBlue,
+      "brightMagenta" => Color::BrightMagenta,
+      "brightCyan" => Color::BrightCyan,
+      "brightWhite" => Color::BrightWhite,
+      _ => return None,
+    })
+  }
+}
+
+/// A combination of colors and text attributes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+  pub fg: Option<Color>,
+  pub bg: Option<Color>,
+  pub bold: bool,
+  pub dim: bool,
+  pub italic: bool,
+  pub underline: bool,
+  pub inverse: bool,
+  pub hidden: bool,
+  pub strikethrough: bool,
+}
+
+impl Style {
+  pub const fn new() -> Self {
+    Style {
+      fg: None,
+      bg: None,
+      bold: false,
+      dim: false,
+      italic: false,
+      underline: false,
+      inverse: false,
+      hidden: false,
+      strikethrough: false,
+    }
+  }
+
+  pub const fn fg(mut self, color: Color) -> Self {
+    self.fg = Some(color);
+    self
+  }
+
+  pub const fn bg(mut self, color: Color) -> Self {
+    self.bg = Some(color);
+    self
+  }
+
+  pub const fn bold(mut self) -> Self {
+    self.bold = true;
+    self
+  }
+
+  pub const fn dim(mut self) -> Self {
+    self.dim = true;
+    self
+  }
+
+  /// Layers `other` on top of this style: its colors replace ours and its
+  /// attributes are added.
+  pub fn patch(self, other: Style) -> Style {
+    Style {
+      fg: other.fg.or(self.fg),
+      bg: other.bg.or(self.bg),
+      bold: self.bold || other.bold,
+      dim: self.dim || other.dim,
+      italic: self.italic || other.italic,
+      underline: self.underline || other.underline,
+      inverse: self.inverse || other.inverse,
+      hidden: self.hidden || other.hidden,
+      strikethrough: self.strikethrough || other.strikethrough,
+    }
+  }
+
+  pub fn is_plain(&self) -> bool {
+    *self == Style::new()
+  }
+
+  /// Wraps `text` so that it is displayed with this style.
+  pub fn paint<T: fmt::Display>(self, text: T) -> Painted<T> {
+    Painted { style: self, text }
+  }
+
+  /// Writes the SGR parameters of the style, separated by `;`.
+  fn write_codes<W: fmt::Write>(&self, level: ColorLevel, f: &mut W) -> fmt::Result {
+    let mut first = true;
+    let mut separate = |f: &mut W| {
+      if !std::mem::take(&mut first) {
+        f.write_str(";")?;
+      }
+      Ok(())
+    };
+    for (enabled, code) in [
+      (self.bold, 1),
+      (self.dim, 2),
+      (self.italic, 3),
+      (self.underline, 4),
+      (self.inverse, 7),
+      (self.hidden, 8),
+      (self.strikethrough, 9),
+    ] {
+      if enabled {
+        separate(f)?;
+        write!(f, "{code}")?;
+      }
+    }
+    if let Some(fg) = self.fg {
+      separate(f)?;
+      fg.degrade(level).write_code(30, f)?;
+    }
+    if let Some(bg) = self.bg {
+      separate(f)?;
+      bg.degrade(level).write_code(40, f)?;
+    }
+    Ok(())
+  }
+
+  /// Applies a single word of a Winston color string, e.g. `bold` or `redBG`.
+  pub fn with_word(mut self, word: &str) -> Result<Self, ParseStyleError> {
+    match word {
+      "reset" => return Ok(Style::new()),
+      "bold" => self.bold = true,
+      "dim" => self.dim = true,
+      "italic" => self.italic = true,
+      "underline" => self.underline = true,
+      "inverse" => self.inverse = true,
+      "hidden" => self.hidden = true,
+      "strikethrough" => self.strikethrough = true,
+      _ => {
+        if let Some(color) = background_name(word).and_then(|name| Color::from_name(&name)) {
+          self.bg = Some(color);
+        } else if let Some(color) = Color::from_name(word) {
+          self.fg = Some(color);
+        } else {
+          return Err(ParseStyleError(word.to_owned()));
+        }
+      }
+    }
+    Ok(self)
+  }
+}
+
+/// Parses Winston's color strings, e.g. `"bold red whiteBG"`.
+///
+/// Words are applied in order, so a later color overrides an earlier one.
+/// Backgrounds may be spelled `whiteBG` or `bgWhite`.
+impl FromStr for Style {
+  type Err = ParseStyleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    s.split_whitespace()
+      .try_fold(Style::new(), Style::with_word)
+  }
+}
+
+/// Turns `whiteBG` or `bgWhite` into the color name `white`.
+fn background_name(word: &str) -> Option<String> {
+  if let Some(name) = word.strip_suffix("BG") {
+    return Some(name.to_owned());
+  }
+  let name = word.strip_prefix("bg")?;
+  let mut chars = name.chars();
+  let first = chars.next()?;
+  Some(first.to_ascii_lowercase().to_string() + chars.as_str())
+}
+
+/// Returned when a color string contains a word that is neither a color nor a
+/// text attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseStyleError(String);
+
+impl fmt::Display for ParseStyleError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "unknown color or style `{}`", self.0)
+  }
+}
+
+impl std::error::Error for ParseStyleError {}
+
+/// Receives styled text piece by piece, so that the same highlighting can
+/// produce escape sequences for a terminal or spans for the TUI.
+pub trait StyledOut {
+  fn push_styled(&mut self, text: &str, style: Style);
+
+  /// Pushes `parts` joined together as one piece of text.
+  fn push_styled_parts(&mut self, parts: &[&str], style: Style) {
+    self.push_styled(&parts.concat(), style);
+  }
+}
+
+/// Appends text wrapped in ANSI escape sequences.
+impl StyledOut for String {
+  fn push_styled(&mut self, text: &str, style: Style) {
+    self.push_styled_parts(&[text], style);
+  }
+
+  fn push_styled_parts(&mut self, parts: &[&str], style: Style) {
+    let level = color_level();
+    if style.is_plain() || level == ColorLevel::None {
+      parts.iter().for_each(|part| self.push_str(part));
+      return;
+    }
+    // The same as `Painted`, without going through a formatter for the text.
+    self.push_str("\x1b[");
+    // Writing to a `String` cannot fail.
+    let _ = style.write_codes(level, self);
+    self.push('m');
+    parts.iter().for_each(|part| self.push_str(part));
+    self.push_str("\x1b[0m");
+  }
+}
+
+/// A value wrapped with the escape sequences of a [`Style`], or left alone if
+/// colors are off (see [`set_color_level`](crate::color::set_color_level)).
+pub struct Painted<T> {
+  style: Style,
+  text: T,
+}
+
+impl<T: fmt::Display> fmt::Display for Painted<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let level = color_level();
+    if self.style.is_plain() || level == ColorLevel::None {
+      return self.text.fmt(f);
+    }
+    f.write_str("\x1b[")?;
+    self.style.write_codes(level, f)?;
+    write!(f, "m{}\x1b[0m", self.text)
+  }
+}
+
+/// Accepts a Winston color string or, like `winston.addColors()`, an array of
+/// words.
+impl<'de> Deserialize<'de> for Style {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Words {
+      One(String),
+      Many(Vec<String>),
+    }
+
+    let words = match Words::deserialize(deserializer)? {
+      Words::One(words) => words,
+      Words::Many(words) => words.join(" "),
+    };
+    words.parse().map_err(de::Error::custom)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn style(json: &str) -> Result<Style, String> {
+    serde_json::from_str(json).map_err(|err| err.to_string())
+  }
+
+  #[test]
+  fn winston_color_strings() {
+    assert_eq!(
+      "bold red whiteBG".parse(),
+      Ok(Style::new().fg(Color::Red).bg(Color::White).bold())
+    );
+    assert_eq!(
+      "bgBrightBlue grey".parse(),
+      Ok(Style::new().fg(Color::Gray).bg(Color::BrightBlue))
+    );
+    assert_eq!("red green".parse(), Ok(Style::new().fg(Color::Green)));
+    assert_eq!("bold reset cyan".parse(), Ok(Style::new().fg(Color::Cyan)));
+    assert_eq!(
+      "#ff8700 bg#005f87".parse(),
+      Ok(
+        Style::new()
+          .fg(Color::Rgb(0xff, 0x87, 0x00))
+          .bg(Color::Rgb(0x00, 0x5f, 0x87))
+      )
+    );
+  }
+
+  #[test]
+  fn unknown_words_are_rejected() {
+    assert_eq!(
+      "bold purple".parse::<Style>(),
+      Err(ParseStyleError("purple".to_owned()))
+    );
+    assert!("#fff".parse::<Style>().is_err());
+    assert!("#gg0000".parse::<Style>().is_err());
+    assert!("bgPurple".parse::<Style>().is_err());
+  }
+
+  #[test]
+  fn styles_are_deserialized_from_strings_and_arrays() {
+    let expected = Style {
+      underline: true,
+      ..Style::new().fg(Color::Magenta).bg(Color::Black)
+    };
+    assert_eq!(style(r#""underline magenta blackBG""#), Ok(expected));
+    assert_eq!(
+      style(r#"["underline", "magenta", "blackBG"]"#),
+      Ok(expected)
+    );
+    assert_eq!(style(r#"["underline magenta", "blackBG"]"#), Ok(expected));
+    let err = style(r#"["bold", "sparkly"]"#).unwrap_err();
+    assert!(err.contains("unknown color or style `sparkly`"), "{err}");
+  }
+}
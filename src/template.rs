@@ -0,0 +1,262 @@
+//! Output templates such as `{timestamp:dim} [{level:upper:pad5}] {label}: {message} {meta}`,
+//! the counterpart of a `winston.format.printf()` layout.
+//!
+//! A placeholder names a (nested) field, optionally followed by `:`-separated
+//! modifiers:
+//!
+//! - `padN` / `lpadN` pad the value to `N` characters, aligned left or right,
+//! - `truncN` cuts the value to at most `N` characters,
+//! - `upper` / `lower` change its case,
+//! - anything else is a Winston color string such as `dim` or `bold red`.
+//!
+//! `{meta}` stands for every field not referenced elsewhere in the template,
+//! and `{{` and `}}` are literal braces.
+
+use std::{fmt, str::FromStr};
+
+use crate::{
+  record::{FieldPath, RESERVED_KEYS},
+  style::{ParseStyleError, Style},
+};
+
+/// The template used when none is given.
+pub const DEFAULT_TEMPLATE: &str = "{timestamp} {level}: {message} {meta}";
+
+/// A parsed output template.
+#[derive(Debug, Clone)]
+pub struct Template {
+  pub(crate) parts: Vec<Part>,
+  /// Top-level keys that are shown by a placeholder and thus left out of
+  /// `{meta}`.
+  pub(crate) shown_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Part {
+  Literal(String),
+  Field(Placeholder),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Placeholder {
+  pub source: Source,
+  /// The style given in the template, applied on top of the default style of
+  /// the field.
+  pub style: Style,
+  pub case: Option<Case>,
+  pub pad: Option<(Align, usize)>,
+  pub truncate: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Source {
+  Level,
+  Timestamp,
+  Message,
+  Meta,
+  Field(FieldPath),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Align {
+  Left,
+  Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Case {
+  Upper,
+  Lower,
+}
+
+/// An invalid template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError(String);
+
+impl fmt::Display for TemplateError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.0)
+  }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl From<ParseStyleError> for TemplateError {
+  fn from(err: ParseStyleError) -> Self {
+    TemplateError(err.to_string())
+  }
+}
+
+impl Default for Template {
+  fn default() -> Self {
+    DEFAULT_TEMPLATE.parse().expect("default template is valid")
+  }
+}
+
+impl FromStr for Template {
+  type Err = TemplateError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut parts = Vec::new();
+    let mut shown_keys = Vec::new();
+    let mut literal = String::new();
+    let mut rest = s;
+    while let Some(i) = rest.find(['{', '}']) {
+      literal.push_str(&rest[..i]);
+      let (brace, after) = rest[i..].split_at(1);
+      if after.starts_with(brace) {
+        literal.push_str(brace);
+        rest = &after[1..];
+        continue;
+      }
+      if brace == "}" {
+        return Err(TemplateError("unmatched `}` in template".to_owned()));
+      }
+      let end = after
+        .find('}')
+        .ok_or_else(|| TemplateError("unclosed `{` in template".to_owned()))?;
+      if !literal.is_empty() {
+        parts.push(Part::Literal(std::mem::take(&mut literal)));
+      }
+      let placeholder = parse_placeholder(&after[..end])?;
+      if let Source::Field(path) = &placeholder.source {
+        shown_keys.push(path.root().to_owned());
+      }
+      parts.push(Part::Field(placeholder));
+      rest = &after[end + 1..];
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+      parts.push(Part::Literal(literal));
+    }
+    Ok(Template { parts, shown_keys })
+  }
+}
+
+impl Template {
+  /// Whether `key` belongs in `{meta}`.
+  pub(crate) fn is_meta(&self, key: &str) -> bool {
+    !RESERVED_KEYS.contains(&key) && !self.shown_keys.iter().any(|shown| shown == key)
+  }
+}
+
+fn parse_placeholder(s: &str) -> Result<Placeholder, TemplateError> {
+  let mut specs = s.split(':');
+  let name = specs.next().unwrap_or_default().trim();
+  let source = match name {
+    "level" => Source::Level,
+    "timestamp" => Source::Timestamp,
+    "message" => Source::Message,
+    "meta" => Source::Meta,
+    _ => Source::Field(
+      FieldPath::parse(name)
+        .ok_or_else(|| TemplateError(format!("invalid field `{name}` in template")))?,
+    ),
+  };
+  let mut placeholder = Placeholder {
+    source,
+    style: Style::new(),
+    case: None,
+    pad: None,
+    truncate: None,
+  };
+  for spec in specs {
+    let spec = spec.trim();
+    if let Some(width) = numeric_suffix(spec, "pad") {
+      placeholder.pad = Some((Align::Left, width));
+    } else if let Some(width) = numeric_suffix(spec, "lpad") {
+      placeholder.pad = Some((Align::Right, width));
+    } else if let Some(width) = numeric_suffix(spec, "trunc") {
+      placeholder.truncate = Some(width);
+    } else if spec == "upper" {
+      placeholder.case = Some(Case::Upper);
+    } else if spec == "lower" {
+      placeholder.case = Some(Case::Lower);
+    } else {
+      placeholder.style = placeholder.style.patch(spec.parse()?);
+    }
+  }
+  Ok(placeholder)
+}
+
+/// Parses specs like `pad5` into `5`.
+fn numeric_suffix(spec: &str, prefix: &str) -> Option<usize> {
+  spec.strip_prefix(prefix)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::style::Color;
+
+  fn parse(s: &str) -> Template {
+    s.parse().unwrap()
+  }
+
+  fn placeholder(s: &str) -> Placeholder {
+    match parse(s).parts.as_slice() {
+      [Part::Field(placeholder)] => placeholder.clone(),
+      parts => panic!("expected a single placeholder: {parts:?}"),
+    }
+  }
+
+  fn literal(part: &Part) -> &str {
+    match part {
+      Part::Literal(text) => text,
+      Part::Field(_) => panic!("expected a literal: {part:?}"),
+    }
+  }
+
+  #[test]
+  fn escaped_braces() {
+    let template = parse("{{{level}}} }}{{");
+    let [open, Part::Field(level), close] = template.parts.as_slice() else {
+      panic!("unexpected parts: {:?}", template.parts);
+    };
+    assert_eq!(literal(open), "{");
+    assert!(matches!(level.source, Source::Level));
+    assert_eq!(literal(close), "} }{");
+    assert_eq!(literal(&parse("{{}}").parts[0]), "{}");
+  }
+
+  #[test]
+  fn unmatched_braces() {
+    let error = |s: &str| s.parse::<Template>().unwrap_err().to_string();
+    assert_eq!(error("{level"), "unclosed `{` in template");
+    assert_eq!(error("level}"), "unmatched `}` in template");
+    assert_eq!(error("{level}}"), "unmatched `}` in template");
+    assert_eq!(error("{a..b}"), "invalid field `a..b` in template");
+    assert!("{level:notacolor}".parse::<Template>().is_err());
+  }
+
+  #[test]
+  fn modifiers() {
+    let level = placeholder("{level:upper:pad5}");
+    assert_eq!(level.case, Some(Case::Upper));
+    assert_eq!(level.pad, Some((Align::Left, 5)));
+
+    let level = placeholder("{ level : lpad7 : lower }");
+    assert!(matches!(level.source, Source::Level));
+    assert_eq!(level.case, Some(Case::Lower));
+    assert_eq!(level.pad, Some((Align::Right, 7)));
+
+    let message = placeholder("{message:trunc20:bold red}");
+    assert_eq!(message.truncate, Some(20));
+    assert_eq!(message.style, Style::new().bold().fg(Color::Red));
+
+    let url = placeholder("{meta.req.url:dim:pad10:lpad3}");
+    assert!(matches!(url.source, Source::Field(_)));
+    assert_eq!(url.style, Style::new().dim());
+    assert_eq!(url.pad, Some((Align::Right, 3)));
+    assert_eq!(url.truncate, None);
+  }
+
+  #[test]
+  fn shown_fields_leave_meta() {
+    let template = parse("{label} {meta.req.url} {message} {meta}");
+    assert!(!template.is_meta("label"));
+    assert!(!template.is_meta("meta"));
+    assert!(!template.is_meta("message"));
+    assert!(template.is_meta("service"));
+  }
+}
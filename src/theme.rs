@@ -0,0 +1,392 @@
+//! Named sets of styles for everything that is rendered.
+
+use std::{collections::HashMap, sync::OnceLock};
+
+use anyhow::bail;
+use serde::Deserialize;
+
+use crate::{
+  level::Levels,
+  style::{Color, Style},
+};
+
+/// The names of the built-in themes.
+pub const BUILTIN: [&str; 4] = ["default", "dark", "light", "solarized"];
+
+/// The styles of every element of the output.
+#[derive(Debug, Clone)]
+pub struct Theme {
+  pub timestamp: Style,
+  pub message: Style,
+  /// Keys of metadata fields.
+  pub key: Style,
+  pub string: Style,
+  pub number: Style,
+  pub boolean: Style,
+  pub null: Style,
+  /// `=`, brackets, commas and colons in metadata.
+  pub punctuation: Style,
+  /// The `Error: message` lines of stack traces.
+  pub stack_header: Style,
+  /// The `file:line:column` of the application's own stack frames.
+  pub stack_location: Style,
+  /// Stack frames inside `node_modules`.
+  pub stack_dependency: Style,
+  /// Stack frames inside Node.js itself.
+  pub stack_internal: Style,
+  /// Lines that are not JSON, with `--non-json dim`.
+  pub dimmed: Style,
+  /// The styles of source names, handed out in order.
+  pub sources: Vec<Style>,
+  /// Styles replacing those of the levels of the same name.
+  pub levels: HashMap<String, Style>,
+}
+
+impl Default for Theme {
+  /// The colors of `winston.format.colorize()` and `util.inspect()`.
+  fn default() -> Self {
+    Theme {
+      timestamp: Style::new().dim(),
+      message: Style::new(),
+      key: Style::new().fg(Color::Blue),
+      string: Style::new().fg(Color::Green),
+      number: Style::new().fg(Color::Yellow),
+      boolean: Style::new().fg(Color::Magenta),
+      null: Style::new().fg(Color::Gray).bold(),
+      punctuation: Style::new().fg(Color::Gray),
+      stack_header: Style::new().fg(Color::Red),
+      stack_location: Style::new().fg(Color::Cyan),
+      stack_dependency: Style::new().fg(Color::Gray),
+      stack_internal: Style::new().fg(Color::Gray).dim(),
+      dimmed: Style::new().dim(),
+      sources: [
+        Color::Cyan,
+        Color::Yellow,
+        Color::Green,
+        Color::Magenta,
+        Color::Blue,
+        Color::BrightCyan,
+        Color::BrightYellow,
+        Color::BrightGreen,
+        Color::BrightMagenta,
+        Color::BrightBlue,
+      ]
+      .map(|color| Style::new().fg(color))
+      .to_vec(),
+      levels: HashMap::new(),
+    }
+  }
+}
+
+impl Theme {
+  /// Looks up a built-in theme.
+  pub fn builtin(name: &str) -> Option<Theme> {
+    match name {
+      "default" => Some(Theme::default()),
+      "dark" => Some(Theme::dark()),
+      "light" => Some(Theme::light()),
+      "solarized" => Some(Theme::solarized()),
+      _ => None,
+    }
+  }
+
+  /// Looks up a theme defined in the config file or built in.
+  pub fn named(name: &str, custom: &HashMap<String, ThemeConfig>) -> anyhow::Result<Theme> {
+    if let Some(config) = custom.get(name) {
+      let base = config.base.as_deref().unwrap_or("default");
+      let Some(mut theme) = Theme::builtin(base) else {
+        bail!(
+          "theme `{name}` is based on unknown theme `{base}`; built-in themes are {}",
+          BUILTIN.join(", ")
+        );
+      };
+      config.apply(&mut theme);
+      return Ok(theme);
+    }
+    match Theme::builtin(name) {
+      Some(theme) => Ok(theme),
+      None => bail!(
+        "unknown theme `{name}`; built-in themes are {}",
+        BUILTIN.join(", ")
+      ),
+    }
+  }
+
+  /// Bright colors that stand out on dark backgrounds.
+  fn dark() -> Theme {
+    Theme {
+      timestamp: Style::new().fg(Color::Gray),
+      key: Style::new().fg(Color::BrightBlue),
+      string: Style::new().fg(Color::BrightGreen),
+      number: Style::new().fg(Color::BrightYellow),
+      boolean: Style::new().fg(Color::BrightMagenta),
+      stack_header: Style::new().fg(Color::BrightRed),
+      stack_location: Style::new().fg(Color::BrightCyan),
+      levels: level_styles(&[
+        (
+          &["emerg", "alert", "crit"],
+          Style::new().fg(Color::BrightRed).bold(),
+        ),
+        (&["error"], Style::new().fg(Color::BrightRed)),
+        (&["warn", "warning"], Style::new().fg(Color::BrightYellow)),
+        (
+          &["notice", "info", "http"],
+          Style::new().fg(Color::BrightGreen),
+        ),
+        (&["verbose", "help"], Style::new().fg(Color::BrightCyan)),
+        (&["debug", "data"], Style::new().fg(Color::BrightBlue)),
+        (
+          &["silly", "prompt", "input"],
+          Style::new().fg(Color::BrightMagenta),
+        ),
+      ]),
+      ..Theme::default()
+    }
+  }
+
+  /// Dark colors that stay readable on light backgrounds, without yellow.
+  fn light() -> Theme {
+    const ORANGE: Color = Color::Rgb(0xaf, 0x5f, 0x00);
+    Theme {
+      timestamp: Style::new().fg(Color::Gray),
+      key: Style::new().fg(Color::Blue),
+      string: Style::new().fg(Color::Rgb(0x00, 0x87, 0x00)),
+      number: Style::new().fg(ORANGE),
+      boolean: Style::new().fg(Color::Magenta),
+      null: Style::new().fg(Color::Gray).bold(),
+      stack_location: Style::new().fg(Color::Rgb(0x00, 0x5f, 0x87)),
+      sources: [
+        Color::Blue,
+        Color::Magenta,
+        Color::Rgb(0x00, 0x87, 0x00),
+        ORANGE,
+        Color::Rgb(0x00, 0x5f, 0x87),
+        Color::Red,
+      ]
+      .map(|color| Style::new().fg(color))
+      .to_vec(),
+      levels: level_styles(&[
+        (
+          &["emerg", "alert", "crit"],
+          Style::new().fg(Color::Red).bold(),
+        ),
+        (&["error"], Style::new().fg(Color::Red)),
+        (&["warn", "warning"], Style::new().fg(ORANGE).bold()),
+        (
+          &["notice", "info", "http"],
+          Style::new().fg(Color::Rgb(0x00, 0x87, 0x00)),
+        ),
+        (
+          &["verbose", "help"],
+          Style::new().fg(Color::Rgb(0x00, 0x5f, 0x87)),
+        ),
+        (&["debug", "data"], Style::new().fg(Color::Blue)),
+        (
+          &["silly", "prompt", "input"],
+          Style::new().fg(Color::Magenta),
+        ),
+      ]),
+      ..Theme::default()
+    }
+  }
+
+  /// The accent colors of Solarized, which work on its light and dark
+  /// backgrounds alike.
+  fn solarized() -> Theme {
+    const BASE01: Color = Color::Rgb(0x58, 0x6e, 0x75);
+    const YELLOW: Color = Color::Rgb(0xb5, 0x89, 0x00);
+    const ORANGE: Color = Color::Rgb(0xcb, 0x4b, 0x16);
+    const RED: Color = Color::Rgb(0xdc, 0x32, 0x2f);
+    const MAGENTA: Color = Color::Rgb(0xd3, 0x36, 0x82);
+    const VIOLET: Color = Color::Rgb(0x6c, 0x71, 0xc4);
+    const BLUE: Color = Color::Rgb(0x26, 0x8b, 0xd2);
+    const CYAN: Color = Color::Rgb(0x2a, 0xa1, 0x98);
+    const GREEN: Color = Color::Rgb(0x85, 0x99, 0x00);
+    Theme {
+      timestamp: Style::new().fg(BASE01),
+      key: Style::new().fg(BLUE),
+      string: Style::new().fg(CYAN),
+      number: Style::new().fg(MAGENTA),
+      boolean: Style::new().fg(VIOLET),
+      null: Style::new().fg(BASE01).bold(),
+      punctuation: Style::new().fg(BASE01),
+      stack_header: Style::new().fg(RED),
+      stack_location: Style::new().fg(CYAN),
+      stack_dependency: Style::new().fg(BASE01),
+      stack_internal: Style::new().fg(BASE01).dim(),
+      dimmed: Style::new().fg(BASE01),
+      sources: [BLUE, MAGENTA, CYAN, ORANGE, VIOLET, GREEN, YELLOW]
+        .map(|color| Style::new().fg(color))
+        .to_vec(),
+      levels: level_styles(&[
+        (&["emerg", "alert", "crit"], Style::new().fg(RED).bold()),
+        (&["error"], Style::new().fg(RED)),
+        (&["warn", "warning"], Style::new().fg(YELLOW)),
+        (&["notice", "info", "http"], Style::new().fg(GREEN)),
+        (&["verbose", "help"], Style::new().fg(CYAN)),
+        (&["debug", "data"], Style::new().fg(BLUE)),
+        (&["silly", "prompt", "input"], Style::new().fg(VIOLET)),
+      ]),
+      ..Theme::default()
+    }
+  }
+
+  /// Replaces the styles of `levels` that the theme has its own for.
+  pub fn apply_levels(&self, levels: &mut Levels) {
+    for (name, &style) in &self.levels {
+      levels.set_style(name, style);
+    }
+  }
+
+  /// The style of the source with the given index.
+  pub fn source(&self, source: usize) -> Style {
+    match self.sources.len() {
+      0 => Style::new(),
+      n => self.sources[source % n],
+    }
+  }
+}
+
+/// The styles of the levels of Winston's `npm`, `syslog` and `cli` configs,
+/// grouped by severity.
+fn level_styles(groups: &[(&[&str], Style)]) -> HashMap<String, Style> {
+  groups
+    .iter()
+    .flat_map(|&(names, style)| names.iter().map(move |&name| (name.to_owned(), style)))
+    .collect()
+}
+
+/// A theme in the config file: a built-in `base` theme with some of its styles
+/// replaced, given as Winston color strings.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct ThemeConfig {
+  pub base: Option<String>,
+  pub timestamp: Option<Style>,
+  pub message: Option<Style>,
+  pub key: Option<Style>,
+  pub string: Option<Style>,
+  pub number: Option<Style>,
+  pub boolean: Option<Style>,
+  pub null: Option<Style>,
+  pub punctuation: Option<Style>,
+  pub stack_header: Option<Style>,
+  pub stack_location: Option<Style>,
+  pub stack_dependency: Option<Style>,
+  pub stack_internal: Option<Style>,
+  pub dimmed: Option<Style>,
+  pub sources: Option<Vec<Style>>,
+  pub levels: HashMap<String, Style>,
+}
+
+impl ThemeConfig {
+  fn apply(&self, theme: &mut Theme) {
+    for (style, custom) in [
+      (&mut theme.timestamp, self.timestamp),
+      (&mut theme.message, self.message),
+      (&mut theme.key, self.key),
+      (&mut theme.string, self.string),
+      (&mut theme.number, self.number),
+      (&mut theme.boolean, self.boolean),
+      (&mut theme.null, self.null),
+      (&mut theme.punctuation, self.punctuation),
+      (&mut theme.stack_header, self.stack_header),
+      (&mut theme.stack_location, self.stack_location),
+      (&mut theme.stack_dependency, self.stack_dependency),
+      (&mut theme.stack_internal, self.stack_internal),
+      (&mut theme.dimmed, self.dimmed),
+    ] {
+      if let Some(custom) = custom {
+        *style = custom;
+      }
+    }
+    if let Some(sources) = &self.sources {
+      theme.sources = sources.clone();
+    }
+    theme.levels.extend(self.levels.clone());
+  }
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Sets the theme everything is rendered with. Only the first call has an
+/// effect.
+pub fn set_theme(theme: Theme) {
+  let _ = THEME.set(theme);
+}
+
+/// The theme set with [`set_theme`], or the default one.
+pub fn theme() -> &'static Theme {
+  THEME.get_or_init(Theme::default)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+
+  fn themes(json: &str) -> HashMap<String, ThemeConfig> {
+    serde_json::from_str(json).unwrap()
+  }
+
+  #[test]
+  fn themes_are_looked_up_by_name() {
+    let custom =
+      themes(r#"{ "mine": { "base": "light" }, "plain": {}, "odd": { "base": "neon" } }"#);
+    assert_eq!(
+      Theme::named("dark", &custom).unwrap().key,
+      Theme::dark().key
+    );
+    assert_eq!(
+      Theme::named("mine", &custom).unwrap().number,
+      Theme::light().number
+    );
+    assert_eq!(
+      Theme::named("plain", &custom).unwrap().key,
+      Theme::default().key
+    );
+    let err = Theme::named("odd", &custom).unwrap_err().to_string();
+    assert!(err.contains("unknown theme `neon`"), "{err}");
+    let err = Theme::named("neon", &custom).unwrap_err().to_string();
+    assert!(err.starts_with("unknown theme `neon`"), "{err}");
+  }
+
+  #[test]
+  fn custom_themes_replace_single_styles() {
+    let custom = themes(
+      r##"{ "mine": {
+        "base": "dark",
+        "key": "#005f87",
+        "stackHeader": "bold red",
+        "sources": ["blue", "magenta"],
+        "levels": { "info": "cyan", "audit": "bold red whiteBG" }
+      } }"##,
+    );
+    let theme = Theme::named("mine", &custom).unwrap();
+    let dark = Theme::dark();
+    assert_eq!(theme.key, Style::new().fg(Color::Rgb(0x00, 0x5f, 0x87)));
+    assert_eq!(theme.stack_header, Style::new().fg(Color::Red).bold());
+    assert_eq!(theme.string, dark.string);
+    assert_eq!(
+      theme.sources,
+      [
+        Style::new().fg(Color::Blue),
+        Style::new().fg(Color::Magenta)
+      ]
+    );
+    assert_eq!(theme.levels["info"], Style::new().fg(Color::Cyan));
+    assert_eq!(theme.levels["error"], dark.levels["error"]);
+    assert_eq!(theme.levels["audit"], "bold red whiteBG".parse().unwrap());
+  }
+
+  #[test]
+  fn configured_colors_win_over_theme_levels() {
+    let config: Config = serde_json::from_str(r#"{ "colors": { "info": "magenta" } }"#).unwrap();
+    let theme = Theme::dark();
+    let mut levels = Levels::npm();
+    theme.apply_levels(&mut levels);
+    config.apply_colors(&mut levels);
+    assert_eq!(levels.style("info"), Style::new().fg(Color::Magenta));
+    assert_eq!(levels.style("error"), theme.levels["error"]);
+  }
+}
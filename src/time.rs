@@ -0,0 +1,366 @@
+//! Parsing the many shapes of Winston timestamps and rendering them again.
+
+use std::{borrow::Cow, str::FromStr, sync::OnceLock};
+
+use anyhow::{anyhow, Context};
+use jiff::{
+  civil::DateTime,
+  fmt::{rfc2822, strtime},
+  tz::TimeZone,
+  SignedDuration, Span, Timestamp, Zoned,
+};
+use serde_json::Value;
+
+/// Epoch values below this are taken to be seconds rather than milliseconds.
+/// It is 1973 in milliseconds, but the year 5138 in seconds.
+const MAX_EPOCH_SECONDS: i64 = 100_000_000_000;
+
+/// Parses the `timestamp` field of a record.
+///
+/// Understands RFC 3339 / ISO 8601 as produced by `winston.format.timestamp()`,
+/// custom layouts like `YYYY-MM-DD HH:mm:ss.SSS` (times without an offset are
+/// taken to be in the system time zone), RFC 2822 as produced by
+/// `Date.toUTCString()`, syslog's `Oct 16 10:00:00`, and epoch seconds or
+/// milliseconds.
+pub fn parse_timestamp(value: &Value) -> Option<Timestamp> {
+  match value {
+    Value::String(s) => parse_timestamp_str(s),
+    Value::Number(n) => match n.as_i64() {
+      Some(epoch) => from_epoch(epoch),
+      None => from_fractional_epoch(n.as_f64()?),
+    },
+    _ => None,
+  }
+}
+
+fn parse_timestamp_str(s: &str) -> Option<Timestamp> {
+  let s = s.trim();
+  if let Ok(timestamp) = s.parse::<Timestamp>() {
+    return Some(timestamp);
+  }
+  if let Ok(datetime) = s.parse::<DateTime>() {
+    return datetime
+      .to_zoned(TimeZone::system())
+      .ok()
+      .map(|zoned| zoned.timestamp());
+  }
+  if let Ok(zoned) = rfc2822::parse(s) {
+    return Some(zoned.timestamp());
+  }
+  if let Some(timestamp) = parse_syslog_time(s) {
+    return Some(timestamp);
+  }
+  if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+    return match s.parse() {
+      Ok(epoch) => from_epoch(epoch),
+      Err(_) => from_fractional_epoch(s.parse().ok()?),
+    };
+  }
+  None
+}
+
+/// Parses the `Oct 16 10:00:00` of syslog and `journalctl`, which has no
+/// year, as local time in the current year.
+fn parse_syslog_time(s: &str) -> Option<Timestamp> {
+  let mut time = strtime::parse("%b %e %H:%M:%S", s).ok()?;
+  let now = Zoned::now();
+  time.set_year(Some(now.year())).ok()?;
+  let datetime = time.to_datetime().ok()?;
+  datetime
+    .to_zoned(now.time_zone().clone())
+    .ok()
+    .map(|zoned| zoned.timestamp())
+}
+
+fn from_epoch(epoch: i64) -> Option<Timestamp> {
+  if epoch.abs() < MAX_EPOCH_SECONDS {
+    Timestamp::from_second(epoch).ok()
+  } else {
+    Timestamp::from_millisecond(epoch).ok()
+  }
+}
+
+/// Epoch seconds or milliseconds with a fractional part, like `1791972004.5`.
+/// The whole part is kept exact, only the fraction goes through a float.
+fn from_fractional_epoch(epoch: f64) -> Option<Timestamp> {
+  let whole = from_epoch(epoch.trunc() as i64)?;
+  let unit = if epoch.abs() < MAX_EPOCH_SECONDS as f64 {
+    1e9
+  } else {
+    1e6
+  };
+  let fraction = SignedDuration::from_nanos((epoch.fract() * unit).round() as i64);
+  whole.checked_add(fraction).ok()
+}
+
+/// Parses the bound of a time window: either a duration like `15m`, `1h30m`
+/// or `2d`, meaning that long before now, or a point in time in any format
+/// [`parse_timestamp`] understands, e.g. `2026-10-16 12:00` or just a date.
+pub fn parse_time_bound(s: &str) -> anyhow::Result<Timestamp> {
+  if let Ok(span) = s.trim().parse::<Span>() {
+    let bound = Zoned::now()
+      .checked_sub(span)
+      .with_context(|| format!("`{s}` reaches too far back"))?;
+    return Ok(bound.timestamp());
+  }
+  parse_timestamp_str(s).ok_or_else(|| anyhow!("`{s}` is neither a time nor a duration like `15m`"))
+}
+
+/// How timestamps are shown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeFormat {
+  /// Exactly as logged.
+  Original,
+  /// A `strftime`-like format such as `%H:%M:%S%.3f`.
+  Strftime(String),
+  /// Relative to now, e.g. `3s ago`.
+  Relative,
+  /// Time since the first record, e.g. `+1.250s`.
+  Elapsed,
+}
+
+impl FromStr for TimeFormat {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Ok(match s {
+      "original" => TimeFormat::Original,
+      "relative" => TimeFormat::Relative,
+      "elapsed" => TimeFormat::Elapsed,
+      "iso" => TimeFormat::Strftime(ISO_FORMAT.to_owned()),
+      _ => {
+        // Catch unknown directives before any output is produced.
+        strtime::format(s, &Timestamp::UNIX_EPOCH.to_zoned(TimeZone::UTC))
+          .with_context(|| format!("invalid time format `{s}`"))?;
+        TimeFormat::Strftime(s.to_owned())
+      }
+    })
+  }
+}
+
+/// What `iso` stands for, and the format used when only a time zone is given.
+const ISO_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f%:z";
+
+/// Parses `local`, `utc` or an IANA time zone name like `Europe/Berlin`,
+/// looked up in the system's time zone database.
+pub fn parse_time_zone(name: &str) -> anyhow::Result<TimeZone> {
+  match name {
+    "local" => Ok(TimeZone::system()),
+    "utc" | "UTC" => Ok(TimeZone::UTC),
+    _ => TimeZone::get(name).map_err(|err| anyhow!("unknown time zone `{name}`: {err}")),
+  }
+}
+
+/// Renders the timestamps of records.
+#[derive(Debug, Clone)]
+pub struct TimeDisplay {
+  format: TimeFormat,
+  tz: TimeZone,
+  /// The timestamp of the first record, for [`TimeFormat::Elapsed`].
+  first: OnceLock<Timestamp>,
+}
+
+impl Default for TimeDisplay {
+  fn default() -> Self {
+    TimeDisplay::new(TimeFormat::Original, TimeZone::system())
+  }
+}
+
+impl TimeDisplay {
+  pub fn new(format: TimeFormat, tz: TimeZone) -> Self {
+    TimeDisplay {
+      format,
+      tz,
+      first: OnceLock::new(),
+    }
+  }
+
+  /// Whether how a timestamp is shown depends on the records before it, so
+  /// that records have to be rendered in order.
+  pub fn is_sequential(&self) -> bool {
+    self.format == TimeFormat::Elapsed
+  }
+
+  /// Whether timestamps are shown as logged.
+  pub fn is_original(&self) -> bool {
+    self.format == TimeFormat::Original
+  }
+
+  /// Renders the `timestamp` field `value`. Values that cannot be parsed are
+  /// shown as they are.
+  pub fn render<'a>(&self, value: &'a Value) -> Cow<'a, str> {
+    let rendered = match &self.format {
+      TimeFormat::Original => None,
+      TimeFormat::Strftime(format) => parse_timestamp(value)
+        .and_then(|timestamp| strtime::format(format, &timestamp.to_zoned(self.tz.clone())).ok()),
+      TimeFormat::Relative => {
+        parse_timestamp(value).map(|timestamp| relative(Timestamp::now().duration_since(timestamp)))
+      }
+      TimeFormat::Elapsed => parse_timestamp(value).map(|timestamp| {
+        let first = *self.first.get_or_init(|| timestamp);
+        let elapsed = timestamp.duration_since(first);
+        let sign = if elapsed.is_negative() { '-' } else { '+' };
+        format!("{sign}{:.3}s", elapsed.abs().as_secs_f64())
+      }),
+    };
+    match rendered {
+      Some(rendered) => Cow::Owned(rendered),
+      None => original(value),
+    }
+  }
+}
+
+fn original(value: &Value) -> Cow<'_, str> {
+  match value {
+    Value::String(s) => Cow::Borrowed(s),
+    other => Cow::Owned(other.to_string()),
+  }
+}
+
+/// Formats the age of a record, e.g. `3s ago`, `5m ago` or `in 2h`.
+fn relative(age: SignedDuration) -> String {
+  let seconds = age.as_secs().unsigned_abs();
+  let amount = match seconds {
+    0..60 => format!("{seconds}s"),
+    60..3600 => format!("{}m", seconds / 60),
+    3600..86400 => format!("{}h", seconds / 3600),
+    _ => format!("{}d", seconds / 86400),
+  };
+  if age.is_negative() {
+    format!("in {amount}")
+  } else {
+    format!("{amount} ago")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  /// `2026-10-14T10:00:04.123Z`.
+  const MILLIS: i64 = 1_791_972_004_123;
+
+  fn utc(s: &str) -> Timestamp {
+    s.parse().unwrap()
+  }
+
+  fn local(s: &str) -> Timestamp {
+    s.parse::<DateTime>()
+      .unwrap()
+      .to_zoned(TimeZone::system())
+      .unwrap()
+      .timestamp()
+  }
+
+  fn parse(value: Value) -> Option<Timestamp> {
+    parse_timestamp(&value)
+  }
+
+  #[test]
+  fn iso() {
+    let expected = Some(utc("2026-10-14T10:00:04.123Z"));
+    assert_eq!(parse(json!("2026-10-14T10:00:04.123Z")), expected);
+    assert_eq!(parse(json!("2026-10-14T12:00:04.123+02:00")), expected);
+    assert_eq!(parse(json!(" 2026-10-14T10:00:04.123Z ")), expected);
+  }
+
+  #[test]
+  fn custom_layout_is_local_time() {
+    assert_eq!(
+      parse(json!("2026-10-14 10:00:04.123")),
+      Some(local("2026-10-14T10:00:04.123"))
+    );
+    assert_eq!(
+      parse(json!("2026-10-14 10:00:04")),
+      Some(local("2026-10-14T10:00:04"))
+    );
+  }
+
+  #[test]
+  fn rfc2822() {
+    assert_eq!(
+      parse(json!("Wed, 14 Oct 2026 10:00:04 GMT")),
+      Some(utc("2026-10-14T10:00:04Z"))
+    );
+    assert_eq!(
+      parse(json!("Wed, 14 Oct 2026 12:00:04 +0200")),
+      Some(utc("2026-10-14T10:00:04Z"))
+    );
+  }
+
+  #[test]
+  fn syslog_is_local_time_this_year() {
+    let year = Zoned::now().year();
+    assert_eq!(
+      parse(json!("Oct 14 10:00:04")),
+      Some(local(&format!("{year}-10-14T10:00:04")))
+    );
+    assert_eq!(
+      parse(json!("Oct  4 09:20:04")),
+      Some(local(&format!("{year}-10-04T09:20:04")))
+    );
+  }
+
+  #[test]
+  fn epoch_milliseconds_are_exact() {
+    let expected = Some(Timestamp::from_millisecond(MILLIS).unwrap());
+    assert_eq!(parse(json!(MILLIS)), expected);
+    assert_eq!(parse(json!(MILLIS.to_string())), expected);
+    assert_eq!(
+      parse(json!(1_791_972_004_123.5)),
+      Some(utc("2026-10-14T10:00:04.1235Z"))
+    );
+  }
+
+  #[test]
+  fn epoch_seconds() {
+    let expected = Some(utc("2026-10-14T10:00:04Z"));
+    assert_eq!(parse(json!(MILLIS / 1000)), expected);
+    assert_eq!(parse(json!((MILLIS / 1000).to_string())), expected);
+    assert_eq!(
+      parse(json!(1_791_972_004.25)),
+      Some(utc("2026-10-14T10:00:04.25Z"))
+    );
+    assert_eq!(
+      parse(json!("1791972004.25")),
+      Some(utc("2026-10-14T10:00:04.25Z"))
+    );
+  }
+
+  #[test]
+  fn not_timestamps() {
+    assert_eq!(parse(json!("yesterday")), None);
+    assert_eq!(parse(json!("")), None);
+    assert_eq!(parse(json!("1.2.3")), None);
+    assert_eq!(parse(json!(true)), None);
+    assert_eq!(parse(json!(null)), None);
+    assert_eq!(parse(json!(i64::MAX)), None);
+  }
+
+  #[test]
+  fn relative_ages() {
+    let secs = SignedDuration::from_secs;
+    assert_eq!(relative(secs(0)), "0s ago");
+    assert_eq!(relative(secs(59)), "59s ago");
+    assert_eq!(relative(secs(60)), "1m ago");
+    assert_eq!(relative(secs(3599)), "59m ago");
+    assert_eq!(relative(secs(7200)), "2h ago");
+    assert_eq!(relative(secs(86400 * 3 + 5)), "3d ago");
+    assert_eq!(relative(secs(-7200)), "in 2h");
+  }
+
+  #[test]
+  fn render_keeps_milliseconds() {
+    let display = TimeDisplay::new(TimeFormat::Strftime(ISO_FORMAT.to_owned()), TimeZone::UTC);
+    assert_eq!(
+      display.render(&json!(MILLIS)),
+      "2026-10-14T10:00:04.123+00:00"
+    );
+    let display = TimeDisplay::new(TimeFormat::Relative, TimeZone::UTC);
+    let hour_ago = Timestamp::now() - SignedDuration::from_hours(1);
+    assert_eq!(display.render(&json!(hour_ago.as_millisecond())), "1h ago");
+    assert_eq!(display.render(&json!("soon")), "soon");
+  }
+}
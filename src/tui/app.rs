@@ -0,0 +1,524 @@
+//! The state of the TUI and how keys change it.
+
+use std::{collections::HashMap, io};
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+  filter::Filter,
+  input::LineSink,
+  level::Levels,
+  printer::NonJson,
+  record::{Record, UNKNOWN_LEVEL},
+  time::TimeDisplay,
+};
+
+/// A line that made it past the filter.
+#[derive(Debug)]
+pub(super) struct Entry {
+  pub source: usize,
+  pub kind: Kind,
+  pub line: String,
+  /// Index into [`App::level_names`], `None` for lines that are not records
+  /// or have no level.
+  pub level: Option<usize>,
+  /// Lines joined onto the record, one per line.
+  pub continuation: String,
+}
+
+/// What an entry's line is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Kind {
+  Record,
+  /// Not JSON, shown as it is.
+  Raw,
+  /// Not JSON, shown dimmed.
+  Dimmed,
+  /// Not JSON, shown as a record with the level `unknown`.
+  Wrapped,
+}
+
+impl Entry {
+  /// The record to show for the entry, if it is or stands for one.
+  pub fn record(&self) -> Option<Record<'_>> {
+    match self.kind {
+      Kind::Record => Record::parse(self.line.as_bytes()),
+      Kind::Wrapped => Some(Record::raw(&self.line)),
+      Kind::Raw | Kind::Dimmed => None,
+    }
+  }
+}
+
+/// Which pane keys go to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Focus {
+  Records,
+  Levels,
+}
+
+/// The optional columns of the record list; the message is always shown.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Columns {
+  pub timestamp: bool,
+  pub level: bool,
+  pub source: bool,
+  pub meta: bool,
+}
+
+/// Progress of reading the inputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Loading {
+  Reading,
+  Following,
+  Done,
+  Failed(String),
+}
+
+#[derive(Debug, Default)]
+pub(super) struct Search {
+  /// The query being typed, while the search prompt is open.
+  pub input: Option<String>,
+  /// The selection when the prompt was opened, where matching starts.
+  origin: usize,
+  pub regex: Option<Regex>,
+  /// Whether the last search found nothing.
+  pub failed: bool,
+}
+
+/// Everything the TUI shows. Lines are fed in through [`LineSink`], keys
+/// through [`App::handle_key`], and [`super::draw`] renders it.
+#[derive(Debug)]
+pub struct App {
+  pub(super) levels: Levels,
+  filter: Filter,
+  pub(super) time: TimeDisplay,
+  pub(super) sources: Vec<String>,
+  pub(super) entries: Vec<Entry>,
+  /// Indices of the entries not hidden by the level toggles, in order.
+  pub(super) visible: Vec<usize>,
+  /// The configured levels, followed by any other levels seen in the input.
+  pub(super) level_names: Vec<String>,
+  level_index: HashMap<String, usize>,
+  pub(super) level_counts: Vec<usize>,
+  pub(super) hidden_levels: Vec<bool>,
+  non_json: NonJson,
+  join: bool,
+  /// With `join`, the entry of the last record, held back while continuation
+  /// lines may still follow, so that they are filtered along with it.
+  pending: Option<Entry>,
+  /// The source of the last record and its entry, unless it was filtered out.
+  last: Option<(usize, Option<usize>)>,
+  /// Whether the last record was inside the filter's time window.
+  in_window: bool,
+  /// The selected row, an index into `visible`.
+  pub(super) selected: usize,
+  /// The first row on screen, kept up to date by drawing.
+  pub(super) offset: usize,
+  /// Rows that fit on screen, for paging.
+  pub(super) page: usize,
+  /// Whether the selection sticks to the last row as lines come in.
+  tail: bool,
+  pub(super) focus: Focus,
+  pub(super) level_cursor: usize,
+  pub(super) columns: Columns,
+  pub(super) detail: bool,
+  pub(super) detail_scroll: u16,
+  pub(super) search: Search,
+  pub(super) loading: Loading,
+  quit: bool,
+}
+
+impl App {
+  /// Creates an empty browser for lines read from inputs called `sources`.
+  pub fn new(levels: Levels, filter: Filter, time: TimeDisplay, sources: Vec<String>) -> Self {
+    let level_names: Vec<String> = levels.iter().map(|level| level.name.clone()).collect();
+    let level_index = level_names
+      .iter()
+      .enumerate()
+      .map(|(i, name)| (name.clone(), i))
+      .collect();
+    let columns = Columns {
+      timestamp: true,
+      level: true,
+      source: sources.len() > 1,
+      meta: true,
+    };
+    App {
+      levels,
+      filter,
+      time,
+      sources,
+      entries: Vec::new(),
+      visible: Vec::new(),
+      level_counts: vec![0; level_names.len()],
+      hidden_levels: vec![false; level_names.len()],
+      level_names,
+      level_index,
+      non_json: NonJson::default(),
+      join: false,
+      pending: None,
+      last: None,
+      in_window: false,
+      selected: 0,
+      offset: 0,
+      page: 1,
+      tail: false,
+      focus: Focus::Records,
+      level_cursor: 0,
+      columns,
+      detail: false,
+      detail_scroll: 0,
+      search: Search::default(),
+      loading: Loading::Reading,
+      quit: false,
+    }
+  }
+
+  /// Sets what to do with lines that are not JSON.
+  pub fn non_json(mut self, policy: NonJson) -> Self {
+    self.non_json = policy;
+    self
+  }
+
+  /// Joins the lines that are not JSON onto the record before them from the
+  /// same source.
+  pub fn join_continuations(mut self, join: bool) -> Self {
+    self.join = join;
+    self
+  }
+
+  /// Keeps the last line selected as lines come in, until the selection is
+  /// moved elsewhere.
+  pub fn tail(mut self, tail: bool) -> Self {
+    self.tail = tail;
+    self
+  }
+
+  pub fn set_loading(&mut self, loading: Loading) {
+    self.loading = loading;
+  }
+
+  pub fn should_quit(&self) -> bool {
+    self.quit
+  }
+
+  /// The entry of the selected row.
+  pub(super) fn selected_entry(&self) -> Option<&Entry> {
+    self.entries.get(*self.visible.get(self.selected)?)
+  }
+
+  pub fn handle_key(&mut self, key: KeyEvent) {
+    if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+      self.quit = true;
+      return;
+    }
+    if self.search.input.is_some() {
+      self.search_key(key);
+      return;
+    }
+    if self.focus == Focus::Levels && self.levels_key(key) {
+      return;
+    }
+    match key.code {
+      KeyCode::Char('q') => self.quit = true,
+      KeyCode::Tab | KeyCode::BackTab => {
+        self.focus = match self.focus {
+          Focus::Records => Focus::Levels,
+          Focus::Levels => Focus::Records,
+        }
+      }
+      KeyCode::Up | KeyCode::Char('k') => self.select(self.selected.saturating_sub(1)),
+      KeyCode::Down | KeyCode::Char('j') => self.select(self.selected + 1),
+      KeyCode::PageUp => self.select(self.selected.saturating_sub(self.page)),
+      KeyCode::PageDown => self.select(self.selected + self.page),
+      KeyCode::Home | KeyCode::Char('g') => self.select(0),
+      KeyCode::End | KeyCode::Char('G') => self.select(usize::MAX),
+      KeyCode::Enter => {
+        self.detail = !self.detail;
+        self.detail_scroll = 0;
+      }
+      KeyCode::Esc => self.detail = false,
+      KeyCode::Char('J') => self.detail_scroll = self.detail_scroll.saturating_add(1),
+      KeyCode::Char('K') => self.detail_scroll = self.detail_scroll.saturating_sub(1),
+      KeyCode::Char('/') => {
+        self.search = Search {
+          input: Some(String::new()),
+          origin: self.selected,
+          ..Search::default()
+        };
+      }
+      KeyCode::Char('n') => self.find_next(self.selected + 1, true),
+      KeyCode::Char('N') => self.find_next(self.selected.wrapping_sub(1), false),
+      KeyCode::Char('1') => self.columns.timestamp = !self.columns.timestamp,
+      KeyCode::Char('2') => self.columns.level = !self.columns.level,
+      KeyCode::Char('3') => self.columns.source = !self.columns.source,
+      KeyCode::Char('4') => self.columns.meta = !self.columns.meta,
+      _ => {}
+    }
+  }
+
+  /// Handles keys for the level sidebar. Returns whether the key was used.
+  fn levels_key(&mut self, key: KeyEvent) -> bool {
+    match key.code {
+      KeyCode::Up | KeyCode::Char('k') => self.level_cursor = self.level_cursor.saturating_sub(1),
+      KeyCode::Down | KeyCode::Char('j') => {
+        self.level_cursor = (self.level_cursor + 1).min(self.level_names.len().saturating_sub(1));
+      }
+      KeyCode::Char(' ') | KeyCode::Enter => {
+        if let Some(hidden) = self.hidden_levels.get_mut(self.level_cursor) {
+          *hidden = !*hidden;
+          self.refilter();
+        }
+      }
+      _ => return false,
+    }
+    true
+  }
+
+  fn search_key(&mut self, key: KeyEvent) {
+    let Some(input) = &mut self.search.input else {
+      return;
+    };
+    match key.code {
+      KeyCode::Enter => self.search.input = None,
+      KeyCode::Esc => {
+        let origin = self.search.origin;
+        self.search = Search::default();
+        self.select(origin);
+      }
+      KeyCode::Backspace => {
+        input.pop();
+        self.update_search();
+      }
+      KeyCode::Char(c) => {
+        input.push(c);
+        self.update_search();
+      }
+      _ => {}
+    }
+  }
+
+  /// Jumps to the first match of the query typed so far.
+  fn update_search(&mut self) {
+    let query = self.search.input.as_deref().unwrap_or_default();
+    self.search.regex = (!query.is_empty()).then(|| {
+      // Smart case: only queries with capitals are case-sensitive.
+      RegexBuilder::new(&regex::escape(query))
+        .case_insensitive(!query.chars().any(char::is_uppercase))
+        .build()
+        .expect("an escaped query is a valid regex")
+    });
+    match self.search.regex {
+      Some(_) => self.find_next(self.search.origin, true),
+      None => {
+        self.search.failed = false;
+        self.select(self.search.origin);
+      }
+    }
+  }
+
+  /// Selects the next row matching the search, starting at row `from` (which
+  /// may be out of range) and wrapping around at either end.
+  fn find_next(&mut self, from: usize, forward: bool) {
+    let Some(regex) = &self.search.regex else {
+      return;
+    };
+    let len = self.visible.len();
+    if len == 0 {
+      self.search.failed = true;
+      return;
+    }
+    let start = if from < len {
+      from
+    } else if forward {
+      0
+    } else {
+      len - 1
+    };
+    let found = (0..len)
+      .map(|step| {
+        if forward {
+          (start + step) % len
+        } else {
+          (start + len - step) % len
+        }
+      })
+      .find(|&row| {
+        let entry = &self.entries[self.visible[row]];
+        regex.is_match(&entry.line) || regex.is_match(&entry.continuation)
+      });
+    self.search.failed = found.is_none();
+    if let Some(row) = found {
+      self.select(row);
+    }
+  }
+
+  /// Moves the selection to `row`, clamped to the rows there are.
+  fn select(&mut self, row: usize) {
+    let last = self.visible.len().saturating_sub(1);
+    let row = row.min(last);
+    if row != self.selected {
+      self.detail_scroll = 0;
+    }
+    self.selected = row;
+    self.tail = row == last;
+  }
+
+  fn is_shown(&self, entry: &Entry) -> bool {
+    entry.level.is_none_or(|level| !self.hidden_levels[level])
+  }
+
+  /// Rebuilds `visible` after levels were toggled, keeping the selection on
+  /// the same entry or the closest one before it.
+  fn refilter(&mut self) {
+    let current = self.visible.get(self.selected).copied();
+    self.visible = (0..self.entries.len())
+      .filter(|&i| self.is_shown(&self.entries[i]))
+      .collect();
+    let row = match current {
+      _ if self.tail => usize::MAX,
+      Some(current) => self
+        .visible
+        .partition_point(|&i| i <= current)
+        .saturating_sub(1),
+      None => 0,
+    };
+    self.select(row);
+  }
+
+  /// Filters the held back record with the lines joined onto it, and adds
+  /// it if it is shown.
+  fn flush_pending(&mut self) {
+    let Some(mut entry) = self.pending.take() else {
+      return;
+    };
+    let Some(mut record) = Record::parse(entry.line.as_bytes()) else {
+      return;
+    };
+    for line in entry.continuation.lines() {
+      record.append_line(line);
+    }
+    if !self.filter.matches(&record) {
+      self.last = Some((entry.source, None));
+      return;
+    }
+    entry.level = self.record_level(&record);
+    drop(record);
+    self.push(entry);
+  }
+
+  fn record_level(&mut self, record: &Record) -> Option<usize> {
+    record
+      .level()
+      .map(|name| self.intern_level(name.into_owned()))
+  }
+
+  /// Adds an entry that made it past the filter.
+  fn push(&mut self, entry: Entry) {
+    if let Some(level) = entry.level {
+      self.level_counts[level] += 1;
+    }
+    if entry.kind == Kind::Record {
+      self.last = Some((entry.source, Some(self.entries.len())));
+    }
+    if self.is_shown(&entry) {
+      self.visible.push(self.entries.len());
+      if self.tail {
+        self.selected = self.visible.len() - 1;
+      }
+    }
+    self.entries.push(entry);
+  }
+
+  fn intern_level(&mut self, name: String) -> usize {
+    if let Some(&index) = self.level_index.get(&name) {
+      return index;
+    }
+    let index = self.level_names.len();
+    self.level_index.insert(name.clone(), index);
+    self.level_names.push(name);
+    self.level_counts.push(0);
+    self.hidden_levels.push(false);
+    index
+  }
+}
+
+impl LineSink for App {
+  fn line(&mut self, source: usize, line: &[u8]) -> io::Result<()> {
+    let (kind, level) = match Record::parse(line) {
+      Some(record) => {
+        self.flush_pending();
+        self.in_window = self.filter.in_time_window(&record);
+        if self.join {
+          self.pending = Some(Entry {
+            source,
+            kind: Kind::Record,
+            line: String::from_utf8_lossy(line).into_owned(),
+            level: None,
+            continuation: String::new(),
+          });
+          return Ok(());
+        }
+        if !self.filter.matches(&record) {
+          self.last = Some((source, None));
+          return Ok(());
+        }
+        (Kind::Record, self.record_level(&record))
+      }
+      None => {
+        let text = String::from_utf8_lossy(line);
+        if self.join {
+          if let Some(pending) = self.pending.as_mut().filter(|entry| entry.source == source) {
+            pending.continuation.push_str(&text);
+            pending.continuation.push('\n');
+            return Ok(());
+          }
+          // The record has already been filtered because the input paused.
+          if let Some((last, entry)) = self.last {
+            if last == source {
+              if let Some(entry) = entry {
+                let continuation = &mut self.entries[entry].continuation;
+                continuation.push_str(&text);
+                continuation.push('\n');
+              }
+              return Ok(());
+            }
+          }
+        }
+        // Like the printer, drop the continuation lines of records outside
+        // the time window.
+        if self.filter.has_time_window() && !self.in_window {
+          return Ok(());
+        }
+        match self.non_json {
+          NonJson::Pass => (Kind::Raw, None),
+          NonJson::Dim => (Kind::Dimmed, None),
+          NonJson::Drop => return Ok(()),
+          NonJson::Wrap => {
+            if !self.filter.matches(&Record::raw(&text)) {
+              return Ok(());
+            }
+            (
+              Kind::Wrapped,
+              Some(self.intern_level(UNKNOWN_LEVEL.to_owned())),
+            )
+          }
+        }
+      }
+    };
+    self.push(Entry {
+      source,
+      kind,
+      line: String::from_utf8_lossy(line).into_owned(),
+      level,
+      continuation: String::new(),
+    });
+    Ok(())
+  }
+
+  fn idle(&mut self) -> io::Result<()> {
+    self.flush_pending();
+    Ok(())
+  }
+}
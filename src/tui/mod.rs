@@ -0,0 +1,419 @@
+//! `winstonjson tui`: an interactive, full-screen log browser.
+//!
+//! [`App`] holds the state and is fed lines like any other [`LineSink`], so it
+//! can be driven and drawn without a terminal, e.g. onto a
+//! `ratatui::backend::TestBackend`.
+
+mod app;
+mod ui;
+
+use std::{
+  io,
+  path::PathBuf,
+  sync::mpsc::{self, Receiver, Sender, TryRecvError},
+  thread,
+  time::{Duration, Instant},
+};
+
+use ratatui::{
+  crossterm::event::{self, Event, KeyEventKind},
+  DefaultTerminal,
+};
+
+pub use self::{
+  app::{App, Loading},
+  ui::draw,
+};
+use crate::input::{follow::follow, merge::merge, read_all, Input, LineSink, OpenOptions};
+
+/// How long to wait for a key before taking in newly read lines.
+const TICK: Duration = Duration::from_millis(100);
+
+/// Lines are handed to the UI thread in batches of this many.
+const BATCH_SIZE: usize = 1024;
+
+/// What to show in the browser.
+#[derive(Debug, Clone)]
+pub struct Source {
+  pub inputs: Vec<Input>,
+  pub options: OpenOptions,
+  /// Merge several inputs by timestamp rather than concatenating them.
+  pub merge: bool,
+  /// Files to keep following once they have been read.
+  pub follow: Vec<PathBuf>,
+}
+
+/// Runs the browser until the user quits. The inputs are read on a
+/// background thread while the UI is already usable.
+pub fn run(mut app: App, source: Source) -> anyhow::Result<()> {
+  let lines = spawn_reader(source);
+  let mut terminal = ratatui::try_init()?;
+  let result = event_loop(&mut terminal, &mut app, &lines);
+  ratatui::restore();
+  result
+}
+
+fn event_loop(
+  terminal: &mut DefaultTerminal,
+  app: &mut App,
+  lines: &Receiver<Message>,
+) -> anyhow::Result<()> {
+  while !app.should_quit() {
+    terminal.draw(|frame| draw(frame, app))?;
+    if event::poll(TICK)? {
+      if let Event::Key(key) = event::read()? {
+        if key.kind == KeyEventKind::Press {
+          app.handle_key(key);
+        }
+      }
+    }
+    // Take in what has been read, but stay responsive while a large file is
+    // being loaded.
+    let deadline = Instant::now() + TICK;
+    while Instant::now() < deadline {
+      match lines.try_recv() {
+        Ok(Message::Lines(batch)) => {
+          for (source, line) in batch {
+            app.line(source, &line)?;
+          }
+        }
+        Ok(Message::Idle) => app.idle()?,
+        Ok(Message::Loading(loading)) => app.set_loading(loading),
+        Err(TryRecvError::Empty | TryRecvError::Disconnected) => break,
+      }
+    }
+  }
+  Ok(())
+}
+
+enum Message {
+  Lines(Vec<(usize, Vec<u8>)>),
+  /// No more input is immediately available.
+  Idle,
+  Loading(Loading),
+}
+
+fn spawn_reader(source: Source) -> Receiver<Message> {
+  let (tx, rx) = mpsc::channel();
+  thread::spawn(move || {
+    let mut sink = ChannelSink {
+      tx: tx.clone(),
+      batch: Vec::new(),
+      busy: false,
+    };
+    let result = read(&source, &mut sink);
+    let _ = sink.idle();
+    let loading = match result {
+      Ok(()) => Loading::Done,
+      Err(err) => Loading::Failed(format!("{err:#}")),
+    };
+    let _ = tx.send(Message::Loading(loading));
+  });
+  rx
+}
+
+fn read(source: &Source, sink: &mut ChannelSink) -> anyhow::Result<()> {
+  if source.merge && source.inputs.len() > 1 {
+    merge(&source.inputs, &source.options, sink)?;
+  } else {
+    read_all(&source.inputs, &source.options, sink)?;
+  }
+  if !source.follow.is_empty() {
+    sink.send()?;
+    let _ = sink.tx.send(Message::Loading(Loading::Following));
+    follow(&source.follow, sink)?;
+  }
+  Ok(())
+}
+
+/// Sends lines to the UI thread.
+struct ChannelSink {
+  tx: Sender<Message>,
+  batch: Vec<(usize, Vec<u8>)>,
+  /// Whether lines have been sent since the UI was last told that the input
+  /// ran dry.
+  busy: bool,
+}
+
+impl ChannelSink {
+  fn send(&mut self) -> io::Result<()> {
+    if self.batch.is_empty() {
+      return Ok(());
+    }
+    let batch = std::mem::take(&mut self.batch);
+    self.busy = true;
+    self.send_message(Message::Lines(batch))
+  }
+
+  fn send_message(&self, message: Message) -> io::Result<()> {
+    // The UI is gone, so there is no point in reading on.
+    self
+      .tx
+      .send(message)
+      .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+  }
+}
+
+impl LineSink for ChannelSink {
+  fn line(&mut self, source: usize, line: &[u8]) -> io::Result<()> {
+    self.batch.push((source, line.to_vec()));
+    if self.batch.len() >= BATCH_SIZE {
+      self.send()?;
+    }
+    Ok(())
+  }
+
+  fn idle(&mut self) -> io::Result<()> {
+    self.send()?;
+    if self.busy {
+      self.busy = false;
+      self.send_message(Message::Idle)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::{backend::TestBackend, crossterm::event::KeyCode, style::Modifier, Terminal};
+
+  use super::*;
+  use crate::{filter::Filter, level::Levels, time::TimeDisplay};
+
+  const LINES: &[&str] = &[
+    r#"{"level":"info","message":"server started"}"#,
+    r#"{"level":"error","message":"request failed: TypeError"}"#,
+    r#"{"level":"warn","message":"slow request"}"#,
+    r#"{"level":"info","message":"retrying after typeerror"}"#,
+    r#"{"level":"debug","message":"cache hit"}"#,
+  ];
+
+  /// A browser with `lines` read from a single file, and a terminal to draw
+  /// it on.
+  fn browser(lines: &[&str]) -> (App, Terminal<TestBackend>) {
+    let mut app = App::new(
+      Levels::npm(),
+      Filter::new(),
+      TimeDisplay::default(),
+      vec!["app.log".to_owned()],
+    );
+    feed(&mut app, lines);
+    (app, Terminal::new(TestBackend::new(100, 12)).unwrap())
+  }
+
+  fn feed(app: &mut App, lines: &[&str]) {
+    for line in lines {
+      app.line(0, line.as_bytes()).unwrap();
+    }
+    app.idle().unwrap();
+  }
+
+  fn keys(app: &mut App, keys: &str) {
+    for c in keys.chars() {
+      app.handle_key(KeyCode::Char(c).into());
+    }
+  }
+
+  /// The rows of the screen as text.
+  fn screen(terminal: &mut Terminal<TestBackend>, app: &mut App) -> Vec<String> {
+    terminal.draw(|frame| draw(frame, app)).unwrap();
+    let buffer = terminal.backend().buffer();
+    (0..buffer.area.height)
+      .map(|y| {
+        (0..buffer.area.width)
+          .map(|x| buffer[(x, y)].symbol())
+          .collect::<String>()
+          .trim_end()
+          .to_owned()
+      })
+      .collect()
+  }
+
+  /// The record rows shown in the list, without the border.
+  fn records(terminal: &mut Terminal<TestBackend>, app: &mut App) -> Vec<String> {
+    let screen = screen(terminal, app);
+    screen[1..screen.len() - 2]
+      .iter()
+      .map(|row| row.chars().skip(25).collect::<String>())
+      .map(|row| row.trim_end_matches([' ', '│']).to_owned())
+      .filter(|row| !row.is_empty())
+      .collect()
+  }
+
+  /// The text of the selected record row, which is drawn reversed.
+  fn selected(terminal: &mut Terminal<TestBackend>, app: &mut App) -> String {
+    let rows = screen(terminal, app);
+    let buffer = terminal.backend().buffer();
+    let y = (1..buffer.area.height)
+      .find(|&y| buffer[(25, y)].modifier.contains(Modifier::REVERSED))
+      .expect("a row is selected");
+    rows[usize::from(y)]
+      .chars()
+      .skip(25)
+      .collect::<String>()
+      .trim_end_matches([' ', '│'])
+      .to_owned()
+  }
+
+  #[test]
+  fn draws_records_and_level_counts() {
+    let (mut app, mut terminal) = browser(LINES);
+    let screen = screen(&mut terminal, &mut app);
+    assert!(screen[0].contains(" Levels "), "{screen:#?}");
+    assert!(screen[0].contains(" 1/5 "), "{screen:#?}");
+    assert!(screen[1].starts_with("│[x] error            1│"));
+    assert!(screen[3].starts_with("│[x] info             2│"));
+    assert_eq!(
+      records(&mut terminal, &mut app),
+      [
+        "info    server started",
+        "error   request failed: TypeError",
+        "warn    slow request",
+        "info    retrying after typeerror",
+        "debug   cache hit",
+      ]
+    );
+    assert_eq!(selected(&mut terminal, &mut app), "info    server started");
+  }
+
+  #[test]
+  fn toggling_a_level_hides_its_records() {
+    let (mut app, mut terminal) = browser(LINES);
+    app.handle_key(KeyCode::Tab.into());
+    keys(&mut app, "jj ");
+    let screen = screen(&mut terminal, &mut app);
+    assert!(screen[3].starts_with("│[ ] info"), "{screen:#?}");
+    assert!(screen[0].contains(" 1/3 "), "{screen:#?}");
+    assert_eq!(
+      records(&mut terminal, &mut app),
+      [
+        "error   request failed: TypeError",
+        "warn    slow request",
+        "debug   cache hit",
+      ]
+    );
+
+    keys(&mut app, " ");
+    assert_eq!(records(&mut terminal, &mut app).len(), 5);
+  }
+
+  #[test]
+  fn search_is_smart_case() {
+    let (mut app, mut terminal) = browser(LINES);
+    keys(&mut app, "/typeerror");
+    assert_eq!(
+      selected(&mut terminal, &mut app),
+      "error   request failed: TypeError"
+    );
+    assert_eq!(screen(&mut terminal, &mut app)[11], "/typeerror");
+
+    // Capitals make the search case-sensitive.
+    app.handle_key(KeyCode::Esc.into());
+    keys(&mut app, "jj/TypeError");
+    assert_eq!(
+      selected(&mut terminal, &mut app),
+      "error   request failed: TypeError"
+    );
+
+    app.handle_key(KeyCode::Esc.into());
+    keys(&mut app, "/Typo");
+    let screen = screen(&mut terminal, &mut app);
+    assert_eq!(screen[11], "/Typo  no match");
+  }
+
+  #[test]
+  fn next_and_previous_match_wrap_around() {
+    let (mut app, mut terminal) = browser(LINES);
+    keys(&mut app, "/request");
+    app.handle_key(KeyCode::Enter.into());
+    assert_eq!(
+      selected(&mut terminal, &mut app),
+      "error   request failed: TypeError"
+    );
+    keys(&mut app, "n");
+    assert_eq!(selected(&mut terminal, &mut app), "warn    slow request");
+    keys(&mut app, "n");
+    assert_eq!(
+      selected(&mut terminal, &mut app),
+      "error   request failed: TypeError"
+    );
+    keys(&mut app, "N");
+    assert_eq!(selected(&mut terminal, &mut app), "warn    slow request");
+    keys(&mut app, "gN");
+    assert_eq!(selected(&mut terminal, &mut app), "warn    slow request");
+  }
+
+  #[test]
+  fn end_follows_new_records() {
+    let (mut app, mut terminal) = browser(LINES);
+    feed(&mut app, &[r#"{"level":"info","message":"unseen"}"#]);
+    assert_eq!(selected(&mut terminal, &mut app), "info    server started");
+
+    keys(&mut app, "G");
+    assert_eq!(selected(&mut terminal, &mut app), "info    unseen");
+    feed(
+      &mut app,
+      &[
+        r#"{"level":"info","message":"one"}"#,
+        r#"{"level":"info","message":"two"}"#,
+        r#"{"level":"info","message":"three"}"#,
+        r#"{"level":"info","message":"four"}"#,
+        r#"{"level":"info","message":"five"}"#,
+      ],
+    );
+    assert_eq!(selected(&mut terminal, &mut app), "info    five");
+    let screen = screen(&mut terminal, &mut app);
+    assert!(screen[0].contains(" 11/11 "), "{screen:#?}");
+    assert_eq!(
+      records(&mut terminal, &mut app).last().unwrap(),
+      "info    five"
+    );
+
+    // Moving up stops following.
+    keys(&mut app, "k");
+    feed(&mut app, &[r#"{"level":"info","message":"six"}"#]);
+    assert_eq!(selected(&mut terminal, &mut app), "info    four");
+  }
+
+  #[test]
+  fn joined_lines_are_filtered_with_their_record() {
+    let filter = Filter::new().expr("message ~ /TypeError/").unwrap();
+    let mut app = App::new(
+      Levels::npm(),
+      filter,
+      TimeDisplay::default(),
+      vec!["app.log".to_owned()],
+    )
+    .join_continuations(true);
+    let mut terminal = Terminal::new(TestBackend::new(100, 12)).unwrap();
+    feed(
+      &mut app,
+      &[
+        r#"{"level":"error","message":"uncaught exception"}"#,
+        "TypeError: cannot read properties of undefined",
+        "    at handler (app.js:10:5)",
+        r#"{"level":"error","message":"uncaught exception"}"#,
+        "RangeError: invalid array length",
+        r#"{"level":"info","message":"TypeError is not a problem here"}"#,
+      ],
+    );
+    assert_eq!(
+      records(&mut terminal, &mut app),
+      [
+        "error   uncaught exception",
+        "info    TypeError is not a problem here",
+      ]
+    );
+    assert_eq!(
+      app.entries[0].continuation,
+      "TypeError: cannot read properties of undefined\n    at handler (app.js:10:5)\n"
+    );
+
+    // Lines that come in after the input paused go to the record before.
+    feed(&mut app, &["    at next (app.js:20:1)"]);
+    assert_eq!(app.entries.len(), 2);
+    assert!(app.entries[1]
+      .continuation
+      .ends_with("at next (app.js:20:1)\n"));
+  }
+}
@@ -0,0 +1,333 @@
+//! Drawing the TUI.
+
+use ratatui::{
+  layout::{Constraint, Layout, Rect},
+  style::{Color as TuiColor, Modifier, Style as TuiStyle},
+  text::{Line, Span},
+  widgets::{Block, Paragraph, Wrap},
+  Frame,
+};
+use serde_json::Value;
+
+use super::app::{App, Entry, Focus, Kind, Loading};
+use crate::{
+  color::{color_level, ColorLevel},
+  meta,
+  render::field_text,
+  stack::write_stack,
+  style::{Color, Style, StyledOut},
+  theme::theme,
+};
+
+const SIDEBAR_WIDTH: u16 = 24;
+const FOCUSED: TuiStyle = TuiStyle::new().fg(TuiColor::Cyan);
+const SELECTED: TuiStyle = TuiStyle::new().add_modifier(Modifier::REVERSED);
+const ERROR: TuiStyle = TuiStyle::new().fg(TuiColor::Red);
+
+/// Renders the whole screen.
+pub fn draw(frame: &mut Frame, app: &mut App) {
+  let [main, status] =
+    Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+  let detail_width = if app.detail {
+    Constraint::Percentage(45)
+  } else {
+    Constraint::Length(0)
+  };
+  let [sidebar, records, detail] = Layout::horizontal([
+    Constraint::Length(SIDEBAR_WIDTH),
+    Constraint::Min(20),
+    detail_width,
+  ])
+  .areas(main);
+  draw_levels(frame, app, sidebar);
+  draw_records(frame, app, records);
+  if app.detail {
+    draw_detail(frame, app, detail);
+  }
+  draw_status(frame, app, status);
+}
+
+fn pane(title: String, focused: bool) -> Block<'static> {
+  let block = Block::bordered().title(title);
+  if focused {
+    block.border_style(FOCUSED)
+  } else {
+    block
+  }
+}
+
+fn draw_levels(frame: &mut Frame, app: &App, area: Rect) {
+  let focused = app.focus == Focus::Levels;
+  let lines: Vec<Line> = app
+    .level_names
+    .iter()
+    .enumerate()
+    .map(|(i, name)| {
+      let check = if app.hidden_levels[i] { "[ ]" } else { "[x]" };
+      let count = app.level_counts[i].to_string();
+      let width = usize::from(SIDEBAR_WIDTH).saturating_sub(7 + count.len());
+      let line = Line::from(vec![
+        Span::raw(format!("{check} ")),
+        Span::styled(
+          format!("{name:width$.width$}"),
+          tui_style(app.levels.style(name)),
+        ),
+        Span::raw(format!(" {count}")),
+      ]);
+      if focused && i == app.level_cursor {
+        line.style(SELECTED)
+      } else {
+        line
+      }
+    })
+    .collect();
+  frame.render_widget(
+    Paragraph::new(lines).block(pane(" Levels ".to_owned(), focused)),
+    area,
+  );
+}
+
+fn draw_records(frame: &mut Frame, app: &mut App, area: Rect) {
+  let block = pane(
+    format!(
+      " {}/{} ",
+      app.visible.len().min(app.selected + 1),
+      app.visible.len()
+    ),
+    app.focus == Focus::Records,
+  );
+  let height = usize::from(block.inner(area).height).max(1);
+  app.page = height;
+  // Scroll just enough to keep the selection on screen.
+  if app.selected < app.offset {
+    app.offset = app.selected;
+  } else if app.selected >= app.offset + height {
+    app.offset = app.selected + 1 - height;
+  }
+  let lines: Vec<Line> = app
+    .visible
+    .iter()
+    .enumerate()
+    .skip(app.offset)
+    .take(height)
+    .map(|(row, &index)| {
+      let line = record_line(app, &app.entries[index]);
+      if row == app.selected {
+        line.style(SELECTED)
+      } else {
+        line
+      }
+    })
+    .collect();
+  frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// One row of the record list, with the columns that are switched on.
+fn record_line(app: &App, entry: &Entry) -> Line<'static> {
+  let mut spans = Spans::default();
+  if app.columns.source {
+    let width = app
+      .sources
+      .iter()
+      .map(|name| name.chars().count())
+      .max()
+      .unwrap_or(0);
+    let name = app.sources.get(entry.source).map_or("", String::as_str);
+    spans.push_styled(&format!("{name:width$} "), theme().source(entry.source));
+  }
+  let Some(record) = entry.record() else {
+    let style = if entry.kind == Kind::Dimmed {
+      theme().dimmed
+    } else {
+      Style::new()
+    };
+    spans.push_styled(&entry.line, style);
+    return Line::from(spans.0);
+  };
+  if app.columns.timestamp {
+    if let Some(timestamp) = record.timestamp() {
+      spans.push_styled(&app.time.render(timestamp), theme().timestamp);
+      spans.push_styled(" ", Style::new());
+    }
+  }
+  if app.columns.level {
+    let width = app
+      .level_names
+      .iter()
+      .map(|name| name.chars().count())
+      .max()
+      .unwrap_or(0);
+    let level = entry
+      .level
+      .map_or("", |level| app.level_names[level].as_str());
+    spans.push_styled(&format!("{level:width$} "), app.levels.style(level));
+  }
+  spans.push_styled(&field_text(record.message()), Style::new());
+  if app.columns.meta {
+    let mut fields = record
+      .meta()
+      .filter(|(key, field)| !(*key == "stack" && field.is_string()))
+      .peekable();
+    if fields.peek().is_some() {
+      spans.push_styled(" ", Style::new());
+      meta::write_inline(&mut spans, fields, Style::new());
+    }
+  }
+  Line::from(spans.0)
+}
+
+fn draw_detail(frame: &mut Frame, app: &App, area: Rect) {
+  let mut lines = Lines::default();
+  if let Some(entry) = app.selected_entry() {
+    match entry.record() {
+      Some(record) => {
+        let value = record.into_value();
+        meta::write_pretty(&mut lines, &value);
+        if let Some(stack) = value.get("stack").and_then(Value::as_str) {
+          lines.push_styled("\n\n", Style::new());
+          write_stack(&mut lines, stack);
+        }
+      }
+      None => lines.push_styled(&entry.line, Style::new()),
+    }
+    if !entry.continuation.is_empty() {
+      lines.push_styled("\n\n", Style::new());
+      lines.push_styled(&entry.continuation, Style::new());
+    }
+  }
+  frame.render_widget(
+    Paragraph::new(lines.finish())
+      .block(pane(" Details ".to_owned(), false))
+      .wrap(Wrap { trim: false })
+      .scroll((app.detail_scroll, 0)),
+    area,
+  );
+}
+
+fn draw_status(frame: &mut Frame, app: &App, area: Rect) {
+  let line = if let Some(input) = &app.search.input {
+    let mut spans = vec![Span::raw(format!("/{input}"))];
+    if app.search.failed {
+      spans.push(Span::styled("  no match", ERROR));
+    }
+    Line::from(spans)
+  } else if app.search.failed {
+    Line::styled("no match", ERROR)
+  } else {
+    match &app.loading {
+      Loading::Failed(err) => Line::styled(err.clone(), ERROR),
+      loading => {
+        let state = match loading {
+          Loading::Reading => "loading… ",
+          Loading::Following => "following ",
+          _ => "",
+        };
+        Line::from(vec![
+          Span::styled(state, TuiStyle::new().add_modifier(Modifier::BOLD)),
+          Span::styled(
+            "q quit  Tab levels  / search  n/N next/prev  Enter details  J/K scroll details  \
+             1-4 time/level/source/meta",
+            TuiStyle::new().add_modifier(Modifier::DIM),
+          ),
+        ])
+      }
+    }
+  };
+  frame.render_widget(Paragraph::new(line), area);
+}
+
+/// Collects styled text as the spans of a single line.
+#[derive(Default)]
+struct Spans(Vec<Span<'static>>);
+
+impl StyledOut for Spans {
+  fn push_styled(&mut self, text: &str, style: Style) {
+    self.0.push(Span::styled(text.to_owned(), tui_style(style)));
+  }
+}
+
+/// Collects styled text as lines, starting a new one at every `\n`.
+#[derive(Default)]
+struct Lines {
+  done: Vec<Line<'static>>,
+  current: Spans,
+}
+
+impl Lines {
+  fn finish(mut self) -> Vec<Line<'static>> {
+    if !self.current.0.is_empty() {
+      self.done.push(Line::from(self.current.0));
+    }
+    self.done
+  }
+}
+
+impl StyledOut for Lines {
+  fn push_styled(&mut self, text: &str, style: Style) {
+    let mut parts = text.split('\n');
+    if let Some(first) = parts.next().filter(|first| !first.is_empty()) {
+      self.current.push_styled(first, style);
+    }
+    for part in parts {
+      self
+        .done
+        .push(Line::from(std::mem::take(&mut self.current.0)));
+      if !part.is_empty() {
+        self.current.push_styled(part, style);
+      }
+    }
+  }
+}
+
+/// The ratatui equivalent of a [`Style`].
+fn tui_style(style: Style) -> TuiStyle {
+  let mut tui = TuiStyle::new();
+  let level = color_level();
+  if level != ColorLevel::None {
+    if let Some(fg) = style.fg {
+      tui = tui.fg(tui_color(fg.degrade(level)));
+    }
+    if let Some(bg) = style.bg {
+      tui = tui.bg(tui_color(bg.degrade(level)));
+    }
+  }
+  for (enabled, modifier) in [
+    (style.bold, Modifier::BOLD),
+    (style.dim, Modifier::DIM),
+    (style.italic, Modifier::ITALIC),
+    (style.underline, Modifier::UNDERLINED),
+    (style.inverse, Modifier::REVERSED),
+    (style.hidden, Modifier::HIDDEN),
+    (style.strikethrough, Modifier::CROSSED_OUT),
+  ] {
+    if enabled {
+      tui = tui.add_modifier(modifier);
+    }
+  }
+  tui
+}
+
+/// ratatui names the basic colors after their bright variants: its `Gray` is
+/// ANSI white and its `White` is bright white.
+fn tui_color(color: Color) -> TuiColor {
+  match color {
+    Color::Black => TuiColor::Black,
+    Color::Red => TuiColor::Red,
+    Color::Green => TuiColor::Green,
+    Color::Yellow => TuiColor::Yellow,
+    Color::Blue => TuiColor::Blue,
+    Color::Magenta => TuiColor::Magenta,
+    Color::Cyan => TuiColor::Cyan,
+    Color::White => TuiColor::Gray,
+    Color::Gray => TuiColor::DarkGray,
+    Color::BrightRed => TuiColor::LightRed,
+    Color::BrightGreen => TuiColor::LightGreen,
+    Color::BrightYellow => TuiColor::LightYellow,
+    Color::BrightBlue => TuiColor::LightBlue,
+    Color::BrightMagenta => TuiColor::LightMagenta,
+    Color::BrightCyan => TuiColor::LightCyan,
+    Color::BrightWhite => TuiColor::White,
+    Color::Fixed(index) => TuiColor::Indexed(index),
+    Color::Rgb(r, g, b) => TuiColor::Rgb(r, g, b),
+  }
+}